@@ -0,0 +1,342 @@
+//! Transitions of the progress bar between levels and of the window as it
+//! appears and disappears, shared by both windows.
+
+use std::time::{Duration, Instant};
+
+use crate::{Easing, WindowAnimation};
+
+/// The level shown by the progress bar while it moves towards the last one
+/// received.
+#[derive(Debug, Clone, Copy)]
+pub struct BarAnimation {
+    from: f64,
+    to: Option<f64>,
+    start: Instant,
+    duration: Duration,
+    easing: Easing,
+}
+impl BarAnimation {
+    /// Starts at `level`, without moving.
+    pub fn new(level: Option<u8>) -> Self {
+        let level = level.map(f64::from);
+        Self {
+            from: level.unwrap_or(0.0),
+            to: level,
+            start: Instant::now(),
+            duration: Duration::ZERO,
+            easing: Easing::default(),
+        }
+    }
+    /// Moves towards `level` from wherever the bar is at `now`. A bar that
+    /// just appeared starts at its level.
+    pub fn set_level(
+        &mut self,
+        level: Option<u8>,
+        duration: Duration,
+        easing: Easing,
+        now: Instant,
+    ) {
+        let level = level.map(f64::from);
+        self.from = match (self.level(now), level) {
+            (Some(current), Some(_)) => current,
+            _ => level.unwrap_or(0.0),
+        };
+        self.to = level;
+        self.start = now;
+        self.duration = duration;
+        self.easing = easing;
+    }
+    /// Returns the level to show at `now`.
+    pub fn level(&self, now: Instant) -> Option<f64> {
+        let to = self.to?;
+        if self.duration.is_zero() {
+            return Some(to);
+        }
+        let elapsed = now.saturating_duration_since(self.start);
+        let progress = self
+            .easing
+            .apply(elapsed.as_secs_f64() / self.duration.as_secs_f64());
+        Some(self.from + (to - self.from) * progress)
+    }
+    /// Whether the bar is still moving at `now`.
+    pub fn is_running(&self, now: Instant) -> bool {
+        self.to.is_some() && now.saturating_duration_since(self.start) < self.duration
+    }
+}
+
+/// How much of the window is shown (0-1) while it appears or disappears.
+#[derive(Debug, Clone, Copy)]
+pub struct Reveal {
+    from: f64,
+    to: f64,
+    start: Instant,
+    duration: Duration,
+}
+impl Reveal {
+    /// Starts fully shown or hidden, without moving.
+    pub fn new(shown: bool) -> Self {
+        let shown = if shown { 1.0 } else { 0.0 };
+        Self {
+            from: shown,
+            to: shown,
+            start: Instant::now(),
+            duration: Duration::ZERO,
+        }
+    }
+    /// Moves towards fully shown from wherever the window is at `now`.
+    /// `duration` is the time to cover the whole way.
+    pub fn show(&mut self, duration: Duration, now: Instant) {
+        self.move_to(1.0, duration, now);
+    }
+    /// Moves towards fully hidden, the same way as `show`.
+    pub fn hide(&mut self, duration: Duration, now: Instant) {
+        self.move_to(0.0, duration, now);
+    }
+    fn move_to(&mut self, to: f64, duration: Duration, now: Instant) {
+        self.from = self.progress(now);
+        self.to = to;
+        self.start = now;
+        // An animation interrupted halfway is reversed in half the time.
+        self.duration = duration.mul_f64((to - self.from).abs());
+    }
+    /// Returns how much of the window to show at `now`.
+    pub fn progress(&self, now: Instant) -> f64 {
+        if self.duration.is_zero() {
+            return self.to;
+        }
+        let elapsed = now.saturating_duration_since(self.start);
+        let t = (elapsed.as_secs_f64() / self.duration.as_secs_f64()).min(1.0);
+        self.from + (self.to - self.from) * t
+    }
+    /// Returns how the window looks at `now`, appearing with `show` and
+    /// disappearing with `hide`. Slides cover `slide_distance` px, or fade
+    /// if there is no side to slide out of.
+    pub fn frame(
+        &self,
+        now: Instant,
+        show: WindowAnimation,
+        hide: WindowAnimation,
+        slide_distance: Option<i32>,
+    ) -> RevealFrame {
+        let shown = self.progress(now);
+        let animation = if self.to < 1.0 { hide } else { show };
+        match (animation, slide_distance) {
+            (WindowAnimation::None, _) => RevealFrame::default(),
+            (WindowAnimation::Slide, Some(distance)) => RevealFrame {
+                offset: ((1.0 - shown) * distance as f64).round() as i32,
+                ..RevealFrame::default()
+            },
+            _ => RevealFrame {
+                opacity: shown,
+                ..RevealFrame::default()
+            },
+        }
+    }
+    /// Whether the window is still moving at `now`.
+    pub fn is_running(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.start) < self.duration
+    }
+}
+
+/// How the window looks at some point of its animation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RevealFrame {
+    pub opacity: f64,
+    /// Distance in px between the window and its place, towards the side it
+    /// slides out of.
+    pub offset: i32,
+}
+impl std::default::Default for RevealFrame {
+    fn default() -> Self {
+        Self {
+            opacity: 1.0,
+            offset: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.unwrap();
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn bar_moves_from_start_to_end() {
+        let start = Instant::now();
+        let mut bar = BarAnimation::new(Some(40));
+        assert_close(bar.level(start), 40.0);
+        assert!(!bar.is_running(start));
+
+        bar.set_level(Some(80), ms(100), Easing::Linear, start);
+        assert_close(bar.level(start), 40.0);
+        assert_close(bar.level(start + ms(50)), 60.0);
+        assert!(bar.is_running(start + ms(50)));
+        assert_close(bar.level(start + ms(100)), 80.0);
+        assert_close(bar.level(start + ms(500)), 80.0);
+        assert!(!bar.is_running(start + ms(100)));
+    }
+
+    #[test]
+    fn bar_follows_the_easing() {
+        let start = Instant::now();
+        let mut bar = BarAnimation::new(Some(0));
+        bar.set_level(Some(100), ms(100), Easing::EaseIn, start);
+        assert_close(bar.level(start + ms(50)), 12.5);
+        bar.set_level(Some(0), ms(100), Easing::EaseOut, start + ms(100));
+        assert_close(bar.level(start + ms(150)), 12.5);
+    }
+
+    #[test]
+    fn bar_retargets_from_where_it_is() {
+        let start = Instant::now();
+        let mut bar = BarAnimation::new(Some(40));
+        bar.set_level(Some(80), ms(100), Easing::Linear, start);
+        bar.set_level(Some(20), ms(100), Easing::Linear, start + ms(50));
+        assert_close(bar.level(start + ms(50)), 60.0);
+        assert_close(bar.level(start + ms(100)), 40.0);
+        assert_close(bar.level(start + ms(150)), 20.0);
+        assert!(!bar.is_running(start + ms(150)));
+    }
+
+    #[test]
+    fn bar_jumps_without_duration() {
+        let start = Instant::now();
+        let mut bar = BarAnimation::new(Some(40));
+        bar.set_level(Some(10), Duration::ZERO, Easing::Linear, start);
+        assert_close(bar.level(start), 10.0);
+        assert!(!bar.is_running(start));
+    }
+
+    #[test]
+    fn bar_appears_at_its_level() {
+        let start = Instant::now();
+        let mut bar = BarAnimation::new(None);
+        assert_eq!(bar.level(start), None);
+        bar.set_level(Some(50), ms(100), Easing::Linear, start);
+        assert_close(bar.level(start), 50.0);
+        assert_close(bar.level(start + ms(50)), 50.0);
+        bar.set_level(None, ms(100), Easing::Linear, start + ms(100));
+        assert_eq!(bar.level(start + ms(150)), None);
+        assert!(!bar.is_running(start + ms(150)));
+    }
+
+    fn frame(reveal: &Reveal, now: Instant, animation: WindowAnimation) -> RevealFrame {
+        reveal.frame(now, animation, animation, Some(100))
+    }
+
+    #[test]
+    fn reveal_fades_in_and_out() {
+        let start = Instant::now();
+        let mut reveal = Reveal::new(false);
+        assert_close(Some(reveal.progress(start)), 0.0);
+        reveal.show(ms(200), start);
+        assert_close(Some(reveal.progress(start + ms(50))), 0.25);
+        assert_eq!(
+            frame(&reveal, start + ms(50), WindowAnimation::Fade),
+            RevealFrame {
+                opacity: 0.25,
+                offset: 0
+            }
+        );
+        assert!(reveal.is_running(start + ms(50)));
+        assert_close(Some(reveal.progress(start + ms(300))), 1.0);
+        assert!(!reveal.is_running(start + ms(200)));
+
+        reveal.hide(ms(200), start + ms(300));
+        let hiding = frame(&reveal, start + ms(450), WindowAnimation::Fade);
+        assert_close(Some(hiding.opacity), 0.25);
+        assert_eq!(hiding.offset, 0);
+        assert_close(Some(reveal.progress(start + ms(500))), 0.0);
+    }
+
+    #[test]
+    fn reveal_slides_in_and_out() {
+        let start = Instant::now();
+        let mut reveal = Reveal::new(false);
+        reveal.show(ms(200), start);
+        assert_eq!(
+            frame(&reveal, start, WindowAnimation::Slide),
+            RevealFrame {
+                opacity: 1.0,
+                offset: 100
+            }
+        );
+        assert_eq!(
+            frame(&reveal, start + ms(50), WindowAnimation::Slide).offset,
+            75
+        );
+        assert_eq!(
+            frame(&reveal, start + ms(200), WindowAnimation::Slide).offset,
+            0
+        );
+
+        reveal.hide(ms(200), start + ms(200));
+        assert_eq!(
+            frame(&reveal, start + ms(250), WindowAnimation::Slide).offset,
+            25
+        );
+        assert_eq!(
+            frame(&reveal, start + ms(400), WindowAnimation::Slide).offset,
+            100
+        );
+    }
+
+    #[test]
+    fn reveal_uses_the_animation_of_its_direction() {
+        let start = Instant::now();
+        let mut reveal = Reveal::new(true);
+        reveal.hide(ms(200), start);
+        let now = start + ms(100);
+        let frame = reveal.frame(
+            now,
+            WindowAnimation::Fade,
+            WindowAnimation::Slide,
+            Some(100),
+        );
+        assert_eq!(frame.offset, 50);
+        // Without a side to slide out of, it fades.
+        let frame = reveal.frame(now, WindowAnimation::Fade, WindowAnimation::Slide, None);
+        assert_eq!(frame.opacity, 0.5);
+        let frame = reveal.frame(now, WindowAnimation::Fade, WindowAnimation::None, Some(100));
+        assert_eq!(frame, RevealFrame::default());
+    }
+
+    #[test]
+    fn reveal_reverses_in_the_time_covered() {
+        let start = Instant::now();
+        let mut reveal = Reveal::new(false);
+        reveal.show(ms(200), start);
+        reveal.hide(ms(200), start + ms(100));
+        assert!(reveal.is_running(start + ms(150)));
+        assert_close(Some(reveal.progress(start + ms(150))), 0.25);
+        assert_close(Some(reveal.progress(start + ms(200))), 0.0);
+        assert!(!reveal.is_running(start + ms(200)));
+    }
+
+    #[test]
+    fn reveal_jumps_without_duration() {
+        let start = Instant::now();
+        let mut reveal = Reveal::new(false);
+        reveal.show(Duration::ZERO, start);
+        assert_close(Some(reveal.progress(start)), 1.0);
+        assert!(!reveal.is_running(start));
+        assert_eq!(
+            frame(&reveal, start, WindowAnimation::Slide),
+            RevealFrame::default()
+        );
+        reveal.hide(Duration::ZERO, start);
+        assert_close(Some(reveal.progress(start)), 0.0);
+        assert_eq!(frame(&reveal, start, WindowAnimation::Fade).opacity, 0.0);
+        // Already hidden, there is nothing to cover.
+        reveal.hide(ms(200), start);
+        assert!(!reveal.is_running(start));
+    }
+}
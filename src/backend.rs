@@ -0,0 +1,220 @@
+use crate::MediaController;
+
+#[derive(Debug)]
+pub enum BackendError {
+    /// The backend doesn't implement the requested operation.
+    Unsupported(&'static str),
+    Io(std::io::Error),
+    Other(String),
+}
+impl std::fmt::Display for BackendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unsupported(operation) => write!(f, "{operation} is not supported"),
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Other(message) => write!(f, "{message}"),
+        }
+    }
+}
+impl std::error::Error for BackendError {}
+impl From<std::io::Error> for BackendError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+impl From<String> for BackendError {
+    fn from(message: String) -> Self {
+        Self::Other(message)
+    }
+}
+impl From<&str> for BackendError {
+    fn from(message: &str) -> Self {
+        Self::Other(message.to_string())
+    }
+}
+
+pub type BackendResult<T> = Result<T, BackendError>;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    #[default]
+    Stopped,
+}
+impl std::fmt::Display for PlaybackStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Playing => write!(f, "playing"),
+            Self::Paused => write!(f, "paused"),
+            Self::Stopped => write!(f, "stopped"),
+        }
+    }
+}
+impl PlaybackStatus {
+    /// Parses `playing`, `paused` or `stopped`, ignoring case.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "playing" => Some(Self::Playing),
+            "paused" => Some(Self::Paused),
+            "stopped" => Some(Self::Stopped),
+            _ => None,
+        }
+    }
+}
+
+/// What a media player is playing.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Track {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub status: PlaybackStatus,
+}
+
+/// Performs the actual media operations on behalf of `MediaControllerApp`.
+///
+/// Every operation has a default implementation that returns
+/// `BackendError::Unsupported`, so a backend only needs to implement the
+/// operations it can handle. The `set_*` operations default to using the
+/// `get_*`, `inc_*` and `toggle_*` ones.
+///
+/// Backends must be `Send`, as the daemon performs actions on the thread that
+/// listens for them.
+pub trait MediaBackend: Send {
+    /// Called with the options in use before any other method, e.g. to pick
+    /// the device to control.
+    fn configure(&mut self, _controller: &MediaController) -> BackendResult<()> {
+        Ok(())
+    }
+
+    /// Should return whether the volume is muted.
+    fn get_volume_mute(&mut self) -> BackendResult<bool> {
+        Err(BackendError::Unsupported("get_volume_mute"))
+    }
+
+    /// Should return whether the microphone is muted.
+    fn get_microphone_mute(&mut self) -> BackendResult<bool> {
+        Err(BackendError::Unsupported("get_microphone_mute"))
+    }
+
+    /// Should return the volume (0-100, or more if boosted).
+    fn get_volume(&mut self) -> BackendResult<u8> {
+        Err(BackendError::Unsupported("get_volume"))
+    }
+    /// Should return the microphone volume (0-100).
+    fn get_microphone_volume(&mut self) -> BackendResult<u8> {
+        Err(BackendError::Unsupported("get_microphone_volume"))
+    }
+    /// Should return the brightness (0-100).
+    fn get_brightness(&mut self) -> BackendResult<u8> {
+        Err(BackendError::Unsupported("get_brightness"))
+    }
+
+    /// Should increment the volume, up to `MediaController::max_volume` as
+    /// given to `configure`. To decrement use a negative value.
+    fn inc_volume(&mut self, _inc: f32) -> BackendResult<()> {
+        Err(BackendError::Unsupported("inc_volume"))
+    }
+    /// Should increment the microphone volume. To decrement use a negative
+    /// value.
+    fn inc_microphone_volume(&mut self, _inc: f32) -> BackendResult<()> {
+        Err(BackendError::Unsupported("inc_microphone_volume"))
+    }
+    /// Should increment the brightness. To decrement use a negative value.
+    fn inc_brightness(&mut self, _inc: f32) -> BackendResult<()> {
+        Err(BackendError::Unsupported("inc_brightness"))
+    }
+
+    /// Should set the volume (0-`MediaController::max_volume`) and unmute.
+    fn set_volume(&mut self, volume: f32) -> BackendResult<()> {
+        let current = self.get_volume()?;
+        self.inc_volume(volume - current as f32)
+    }
+    /// Should set the microphone volume (0-100) and unmute it.
+    fn set_microphone_volume(&mut self, volume: f32) -> BackendResult<()> {
+        let current = self.get_microphone_volume()?;
+        self.inc_microphone_volume(volume - current as f32)
+    }
+    /// Should set the brightness (0-100).
+    fn set_brightness(&mut self, brightness: f32) -> BackendResult<()> {
+        let current = self.get_brightness()?;
+        self.inc_brightness(brightness - current as f32)
+    }
+
+    /// Should toggle volume mute.
+    fn toggle_volume_mute(&mut self) -> BackendResult<()> {
+        Err(BackendError::Unsupported("toggle_volume_mute"))
+    }
+
+    /// Should toggle microphone mute.
+    fn toggle_microphone_mute(&mut self) -> BackendResult<()> {
+        Err(BackendError::Unsupported("toggle_microphone_mute"))
+    }
+
+    /// Should mute or unmute the volume.
+    fn set_volume_mute(&mut self, mute: bool) -> BackendResult<()> {
+        if self.get_volume_mute()? != mute {
+            self.toggle_volume_mute()?;
+        }
+        Ok(())
+    }
+    /// Should mute or unmute the microphone.
+    fn set_microphone_mute(&mut self, mute: bool) -> BackendResult<()> {
+        if self.get_microphone_mute()? != mute {
+            self.toggle_microphone_mute()?;
+        }
+        Ok(())
+    }
+
+    /// Should return the keyboard backlight brightness (0-100).
+    fn get_keyboard_brightness(&mut self) -> BackendResult<u8> {
+        Err(BackendError::Unsupported("get_keyboard_brightness"))
+    }
+    /// Should increment the keyboard backlight brightness. To decrement use a
+    /// negative value.
+    fn inc_keyboard_brightness(&mut self, _inc: f32) -> BackendResult<()> {
+        Err(BackendError::Unsupported("inc_keyboard_brightness"))
+    }
+    /// Should set the keyboard backlight brightness (0-100).
+    fn set_keyboard_brightness(&mut self, brightness: f32) -> BackendResult<()> {
+        let current = self.get_keyboard_brightness()?;
+        self.inc_keyboard_brightness(brightness - current as f32)
+    }
+    /// Should turn the keyboard backlight off, or back on if it is off.
+    fn toggle_keyboard_backlight(&mut self) -> BackendResult<()> {
+        Err(BackendError::Unsupported("toggle_keyboard_backlight"))
+    }
+
+    /// Should return the description of the output device, e.g. "Built-in
+    /// Audio Analog Stereo".
+    fn get_volume_device(&mut self) -> BackendResult<String> {
+        Err(BackendError::Unsupported("get_volume_device"))
+    }
+    /// Should return the description of the input device.
+    fn get_microphone_device(&mut self) -> BackendResult<String> {
+        Err(BackendError::Unsupported("get_microphone_device"))
+    }
+    /// Should make the next output device the default one, going back to the
+    /// first after the last.
+    fn next_volume_device(&mut self) -> BackendResult<()> {
+        Err(BackendError::Unsupported("next_volume_device"))
+    }
+
+    /// Should return what the media player is playing.
+    fn get_track(&mut self) -> BackendResult<Track> {
+        Err(BackendError::Unsupported("get_track"))
+    }
+
+    /// Should toggle between playing and paused.
+    fn play_pause(&mut self) -> BackendResult<()> {
+        Err(BackendError::Unsupported("play_pause"))
+    }
+    /// Should skip to the next track.
+    fn next_track(&mut self) -> BackendResult<()> {
+        Err(BackendError::Unsupported("next_track"))
+    }
+    /// Should go back to the previous track.
+    fn previous_track(&mut self) -> BackendResult<()> {
+        Err(BackendError::Unsupported("previous_track"))
+    }
+}
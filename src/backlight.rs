@@ -0,0 +1,397 @@
+use std::path::{Path, PathBuf};
+
+use crate::{BackendError, BackendResult, MediaBackend, MediaController};
+
+pub const BACKLIGHT_CLASS: &str = "/sys/class/backlight";
+pub const LEDS_CLASS: &str = "/sys/class/leds";
+
+const BUSCTL: &str = "busctl";
+/// Keyboard backlights are the LEDs with this in their name, e.g.
+/// `tpacpi::kbd_backlight` or `asus::kbd_backlight`.
+const KEYBOARD_LED: &str = "kbd_backlight";
+
+/// Same preference order as systemd: firmware interfaces know best how to
+/// drive the panel, raw ones are the last resort.
+const TYPE_PRIORITY: &[&str] = &["firmware", "platform", "raw"];
+
+/// Controls a backlight through the sysfs class interface, e.g.
+/// `/sys/class/backlight/intel_backlight`.
+///
+/// The brightness is written directly when permitted, otherwise through
+/// logind's `SetBrightness`, which is allowed for the active session.
+pub struct BacklightBackend {
+    root: PathBuf,
+    /// Name of the class, as logind expects it.
+    subsystem: &'static str,
+    /// What the devices are called in error messages.
+    kind: &'static str,
+    /// Only the devices with this in their name are considered.
+    filter: Option<&'static str>,
+    device: Option<String>,
+}
+impl BacklightBackend {
+    /// Uses the devices found in `/sys/class/backlight`.
+    pub fn new() -> Self {
+        Self::with_root(BACKLIGHT_CLASS)
+    }
+    /// Uses the devices found in `root`, which must have the same layout as
+    /// `/sys/class/backlight`.
+    pub fn with_root(root: impl AsRef<Path>) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            subsystem: "backlight",
+            kind: "backlight",
+            filter: None,
+            device: None,
+        }
+    }
+    /// Uses the keyboard backlights found in `root`, which must have the same
+    /// layout as `/sys/class/leds`.
+    fn keyboard_with_root(root: impl AsRef<Path>) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            subsystem: "leds",
+            kind: "keyboard backlight",
+            filter: Some(KEYBOARD_LED),
+            device: None,
+        }
+    }
+    /// Picks the device to control. `None` selects one automatically.
+    pub fn set_device(&mut self, device: Option<String>) {
+        self.device = device;
+    }
+    /// Returns the names of the available devices, sorted.
+    pub fn devices(&self) -> BackendResult<Vec<String>> {
+        let entries = std::fs::read_dir(&self.root)
+            .map_err(|err| format!("failed to read {}: {err}", self.root.display()))?;
+        let mut devices = entries
+            .flatten()
+            .filter(|entry| entry.path().join("max_brightness").exists())
+            .filter(|entry| match self.filter {
+                Some(filter) => entry.file_name().to_string_lossy().contains(filter),
+                None => true,
+            })
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        devices.sort();
+        Ok(devices)
+    }
+    fn device(&self) -> BackendResult<String> {
+        let devices = self.devices()?;
+        if let Some(device) = &self.device {
+            if devices.contains(device) {
+                return Ok(device.clone());
+            }
+            return Err(format!(
+                "{} device {device} not found (available: {})",
+                self.kind,
+                devices.join(", ")
+            )
+            .into());
+        }
+        let priority = |device: &String| {
+            let kind =
+                std::fs::read_to_string(self.root.join(device).join("type")).unwrap_or_default();
+            TYPE_PRIORITY
+                .iter()
+                .position(|t| *t == kind.trim())
+                .unwrap_or(TYPE_PRIORITY.len())
+        };
+        devices
+            .iter()
+            .min_by_key(|device| priority(device))
+            .cloned()
+            .ok_or_else(|| {
+                format!("no {} device found in {}", self.kind, self.root.display()).into()
+            })
+    }
+    fn read_value(path: &Path) -> BackendResult<u32> {
+        let value = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+        value
+            .trim()
+            .parse()
+            .map_err(|err| format!("invalid value in {}: {err}", path.display()).into())
+    }
+    fn write_value(&self, device: &str, value: u32) -> BackendResult<()> {
+        let path = self.root.join(device).join("brightness");
+        match std::fs::write(&path, value.to_string()) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => {
+                self.logind_set_brightness(device, value)
+            }
+            Err(err) => Err(format!("failed to write {}: {err}", path.display()).into()),
+        }
+    }
+    fn logind_set_brightness(&self, device: &str, value: u32) -> BackendResult<()> {
+        let output = std::process::Command::new(BUSCTL)
+            .args([
+                "call",
+                "org.freedesktop.login1",
+                "/org/freedesktop/login1/session/auto",
+                "org.freedesktop.login1.Session",
+                "SetBrightness",
+                "ssu",
+                self.subsystem,
+                device,
+                &value.to_string(),
+            ])
+            .output()
+            .map_err(|err| format!("failed to run {BUSCTL}: {err}"))?;
+        if !output.status.success() {
+            return Err(BackendError::from(format!(
+                "logind refused to set the brightness: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+    /// Returns the device along with its current and maximum raw values.
+    fn read_state(&self) -> BackendResult<(String, u32, u32)> {
+        let device = self.device()?;
+        let dir = self.root.join(&device);
+        let brightness = Self::read_value(&dir.join("brightness"))?;
+        let max_brightness = Self::read_value(&dir.join("max_brightness"))?;
+        if max_brightness == 0 {
+            return Err(format!("{device} reports a max_brightness of 0").into());
+        }
+        Ok((device, brightness, max_brightness))
+    }
+}
+impl std::default::Default for BacklightBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl MediaBackend for BacklightBackend {
+    fn configure(&mut self, controller: &MediaController) -> BackendResult<()> {
+        self.set_device(controller.backlight.clone());
+        Ok(())
+    }
+    fn get_brightness(&mut self) -> BackendResult<u8> {
+        let (_, brightness, max_brightness) = self.read_state()?;
+        let percentage = brightness.min(max_brightness) as f32 * 100.0 / max_brightness as f32;
+        Ok(percentage.round() as u8)
+    }
+    fn set_brightness(&mut self, brightness: f32) -> BackendResult<()> {
+        let (device, _, max_brightness) = self.read_state()?;
+        let value = (brightness.clamp(0.0, 100.0) * max_brightness as f32 / 100.0).round() as u32;
+        self.write_value(&device, value)
+    }
+    fn inc_brightness(&mut self, inc: f32) -> BackendResult<()> {
+        let (device, brightness, max_brightness) = self.read_state()?;
+        let delta = (inc.abs() * max_brightness as f32 / 100.0).round() as u32;
+        // Always move at least one step so that small increments on devices
+        // with few levels aren't lost to rounding.
+        let delta = delta.max((inc != 0.0) as u32);
+        let value = if inc < 0.0 {
+            brightness.saturating_sub(delta)
+        } else {
+            brightness.saturating_add(delta).min(max_brightness)
+        };
+        self.write_value(&device, value)
+    }
+}
+
+/// Controls a keyboard backlight through the LED class interface, e.g.
+/// `/sys/class/leds/tpacpi::kbd_backlight`.
+///
+/// Many of them only have a few levels, e.g. 0-3, so every increment moves at
+/// least one level.
+pub struct KeyboardBacklightBackend {
+    backlight: BacklightBackend,
+}
+impl KeyboardBacklightBackend {
+    /// Uses the keyboard backlights found in `/sys/class/leds`.
+    pub fn new() -> Self {
+        Self::with_root(LEDS_CLASS)
+    }
+    /// Uses the keyboard backlights found in `root`, which must have the same
+    /// layout as `/sys/class/leds`.
+    pub fn with_root(root: impl AsRef<Path>) -> Self {
+        Self {
+            backlight: BacklightBackend::keyboard_with_root(root),
+        }
+    }
+    /// Picks the device to control. `None` selects one automatically.
+    pub fn set_device(&mut self, device: Option<String>) {
+        self.backlight.set_device(device);
+    }
+    /// Returns the names of the available devices, sorted.
+    pub fn devices(&self) -> BackendResult<Vec<String>> {
+        self.backlight.devices()
+    }
+}
+impl std::default::Default for KeyboardBacklightBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl MediaBackend for KeyboardBacklightBackend {
+    fn configure(&mut self, controller: &MediaController) -> BackendResult<()> {
+        self.set_device(controller.keyboard_backlight.clone());
+        Ok(())
+    }
+    fn get_keyboard_brightness(&mut self) -> BackendResult<u8> {
+        self.backlight.get_brightness()
+    }
+    fn set_keyboard_brightness(&mut self, brightness: f32) -> BackendResult<()> {
+        self.backlight.set_brightness(brightness)
+    }
+    fn inc_keyboard_brightness(&mut self, inc: f32) -> BackendResult<()> {
+        self.backlight.inc_brightness(inc)
+    }
+    fn toggle_keyboard_backlight(&mut self) -> BackendResult<()> {
+        let (device, brightness, max_brightness) = self.backlight.read_state()?;
+        // The level before turning it off isn't kept, it comes back fully on.
+        let value = if brightness > 0 { 0 } else { max_brightness };
+        self.backlight.write_value(&device, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory laid out like a sysfs class, removed when dropped.
+    struct FakeClass {
+        root: PathBuf,
+    }
+    impl FakeClass {
+        fn new(name: &str) -> Self {
+            let root = std::env::temp_dir().join(format!(
+                "media-controller-test-{}-{name}",
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&root);
+            std::fs::create_dir_all(&root).unwrap();
+            Self { root }
+        }
+        fn add(&self, device: &str, kind: Option<&str>, brightness: u32, max_brightness: u32) {
+            let dir = self.root.join(device);
+            std::fs::create_dir_all(&dir).unwrap();
+            if let Some(kind) = kind {
+                std::fs::write(dir.join("type"), format!("{kind}\n")).unwrap();
+            }
+            std::fs::write(dir.join("brightness"), format!("{brightness}\n")).unwrap();
+            std::fs::write(dir.join("max_brightness"), format!("{max_brightness}\n")).unwrap();
+        }
+        fn brightness(&self, device: &str) -> u32 {
+            BacklightBackend::read_value(&self.root.join(device).join("brightness")).unwrap()
+        }
+    }
+    impl Drop for FakeClass {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn prefers_firmware_then_platform_then_raw() {
+        let class = FakeClass::new("priority");
+        class.add("acpi_video0", Some("raw"), 1, 10);
+        class.add("intel_backlight", Some("platform"), 2, 10);
+        class.add("unknown", None, 3, 10);
+        let backend = BacklightBackend::with_root(&class.root);
+        assert_eq!(backend.device().unwrap(), "intel_backlight");
+
+        class.add("acpi_firmware", Some("firmware"), 4, 10);
+        assert_eq!(backend.device().unwrap(), "acpi_firmware");
+    }
+
+    #[test]
+    fn lists_the_available_devices_when_missing() {
+        let class = FakeClass::new("missing");
+        class.add("intel_backlight", Some("raw"), 1, 10);
+        class.add("nvidia_0", Some("raw"), 1, 10);
+        // Not a device.
+        std::fs::create_dir(class.root.join("power")).unwrap();
+        let mut backend = BacklightBackend::with_root(&class.root);
+        backend.set_device(Some("amdgpu_bl0".to_string()));
+        assert_eq!(
+            backend.get_brightness().unwrap_err().to_string(),
+            "backlight device amdgpu_bl0 not found (available: intel_backlight, nvidia_0)"
+        );
+
+        let empty = FakeClass::new("empty");
+        let mut backend = BacklightBackend::with_root(&empty.root);
+        assert!(backend
+            .get_brightness()
+            .unwrap_err()
+            .to_string()
+            .starts_with("no backlight device found in"));
+    }
+
+    #[test]
+    fn converts_to_and_from_percentages() {
+        let class = FakeClass::new("percentage");
+        class.add("intel_backlight", None, 937, 1875);
+        let mut backend = BacklightBackend::with_root(&class.root);
+        // 49.97% rounds to 50%.
+        assert_eq!(backend.get_brightness().unwrap(), 50);
+        backend.set_brightness(33.0).unwrap();
+        assert_eq!(class.brightness("intel_backlight"), 619);
+        backend.set_brightness(150.0).unwrap();
+        assert_eq!(class.brightness("intel_backlight"), 1875);
+        // Values past the maximum are read as 100%.
+        class.add("intel_backlight", None, 2000, 1875);
+        assert_eq!(backend.get_brightness().unwrap(), 100);
+    }
+
+    #[test]
+    fn rejects_a_max_brightness_of_0() {
+        let class = FakeClass::new("zero");
+        class.add("broken", None, 0, 0);
+        let mut backend = BacklightBackend::with_root(&class.root);
+        assert_eq!(
+            backend.get_brightness().unwrap_err().to_string(),
+            "broken reports a max_brightness of 0"
+        );
+    }
+
+    #[test]
+    fn increments_move_at_least_one_step() {
+        let class = FakeClass::new("step");
+        class.add("intel_backlight", None, 50, 100);
+        let mut backend = BacklightBackend::with_root(&class.root);
+        backend.inc_brightness(0.2).unwrap();
+        assert_eq!(class.brightness("intel_backlight"), 51);
+        backend.inc_brightness(-0.2).unwrap();
+        assert_eq!(class.brightness("intel_backlight"), 50);
+        backend.inc_brightness(80.0).unwrap();
+        assert_eq!(class.brightness("intel_backlight"), 100);
+        backend.inc_brightness(-120.0).unwrap();
+        assert_eq!(class.brightness("intel_backlight"), 0);
+    }
+
+    #[test]
+    fn keyboard_backlight_with_few_levels() {
+        let class = FakeClass::new("keyboard");
+        class.add("input3::capslock", None, 0, 1);
+        class.add("tpacpi::kbd_backlight", None, 0, 3);
+        let mut backend = KeyboardBacklightBackend::with_root(&class.root);
+        assert_eq!(backend.devices().unwrap(), ["tpacpi::kbd_backlight"]);
+
+        // 5% of 3 levels rounds to nothing, but still moves one level.
+        backend.inc_keyboard_brightness(5.0).unwrap();
+        assert_eq!(class.brightness("tpacpi::kbd_backlight"), 1);
+        assert_eq!(backend.get_keyboard_brightness().unwrap(), 33);
+        backend.inc_keyboard_brightness(50.0).unwrap();
+        assert_eq!(class.brightness("tpacpi::kbd_backlight"), 3);
+        backend.inc_keyboard_brightness(5.0).unwrap();
+        assert_eq!(class.brightness("tpacpi::kbd_backlight"), 3);
+        backend.inc_keyboard_brightness(-5.0).unwrap();
+        assert_eq!(class.brightness("tpacpi::kbd_backlight"), 2);
+
+        backend.toggle_keyboard_backlight().unwrap();
+        assert_eq!(class.brightness("tpacpi::kbd_backlight"), 0);
+        backend.toggle_keyboard_backlight().unwrap();
+        assert_eq!(class.brightness("tpacpi::kbd_backlight"), 3);
+
+        backend.set_device(Some("input3::capslock".to_string()));
+        assert_eq!(
+            backend.get_keyboard_brightness().unwrap_err().to_string(),
+            "keyboard backlight device input3::capslock not found (available: tpacpi::kbd_backlight)"
+        );
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{Action, Color, MediaController};
+use crate::config::Config;
+use crate::{
+    Action, Anchor, Color, Curve, Easing, Icon, MediaController, Monitor, WindowAnimation,
+};
 
 pub const NAME: &str = env!("CARGO_PKG_NAME");
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -7,120 +10,395 @@ pub const TAB: &str = "    ";
 
 const ARG_WIDTH: &str = "width";
 const ARG_HEIGHT: &str = "height";
+const ARG_MONITOR: &str = "monitor";
+const ARG_ANCHOR: &str = "anchor";
+const ARG_TOP: &str = "top";
 const ARG_BOTTOM: &str = "bottom";
+const ARG_LEFT: &str = "left";
+const ARG_RIGHT: &str = "right";
 const ARG_FONT_DESCRIPTION: &str = "font-description";
 const ARG_COLOR: &str = "color";
 const ARG_DURATION: &str = "duration";
+const ARG_SHOW_DURATION: &str = "show-duration";
+const ARG_SHOW_ANIMATION: &str = "show-animation";
+const ARG_HIDE_DURATION: &str = "hide-duration";
+const ARG_HIDE_ANIMATION: &str = "hide-animation";
 const ARG_FILLED: &str = "filled";
 const ARG_HALF_FILLED: &str = "half-filled";
 const ARG_EMPTY: &str = "empty";
+const ARG_OVER_FILLED: &str = "over-filled";
+const ARG_GRAPHIC_BAR: &str = "graphic-bar";
+const ARG_BAR_FILL_COLOR: &str = "bar-fill-color";
+const ARG_BAR_TRACK_COLOR: &str = "bar-track-color";
+const ARG_BAR_BORDER_COLOR: &str = "bar-border-color";
+const ARG_BAR_OVER_COLOR: &str = "bar-over-color";
+const ARG_TRANSITION_DURATION: &str = "transition-duration";
+const ARG_TRANSITION_EASING: &str = "transition-easing";
+const ARG_ICON: &str = "icon";
+const ARG_MUTED_ICON: &str = "muted-icon";
+const ARG_BACKLIGHT: &str = "backlight";
+const ARG_DDC: &str = "ddc";
+const ARG_KEYBOARD_BACKLIGHT: &str = "keyboard-backlight";
+const ARG_PLAYER: &str = "player";
+const ARG_DEVICE: &str = "device";
+const ARG_MAX_VOLUME: &str = "max-volume";
+const ARG_VOLUME_CURVE: &str = "volume-curve";
+const ARG_BRIGHTNESS_CURVE: &str = "brightness-curve";
+const ARG_PERCEIVED_PROGRESS: &str = "perceived-progress";
+const ARG_CONFIG: &str = "config";
+const ARG_DAEMON: &str = "daemon";
+const ARG_NAMESPACE: &str = "namespace";
 
 const ARGS: &[&str] = &[
     ARG_WIDTH,
     ARG_HEIGHT,
+    ARG_MONITOR,
+    ARG_ANCHOR,
+    ARG_TOP,
     ARG_BOTTOM,
+    ARG_LEFT,
+    ARG_RIGHT,
     ARG_FONT_DESCRIPTION,
     ARG_COLOR,
     ARG_DURATION,
+    ARG_SHOW_DURATION,
+    ARG_SHOW_ANIMATION,
+    ARG_HIDE_DURATION,
+    ARG_HIDE_ANIMATION,
     ARG_FILLED,
     ARG_HALF_FILLED,
     ARG_EMPTY,
+    ARG_OVER_FILLED,
+    ARG_GRAPHIC_BAR,
+    ARG_BAR_FILL_COLOR,
+    ARG_BAR_TRACK_COLOR,
+    ARG_BAR_BORDER_COLOR,
+    ARG_BAR_OVER_COLOR,
+    ARG_TRANSITION_DURATION,
+    ARG_TRANSITION_EASING,
+    ARG_ICON,
+    ARG_MUTED_ICON,
+    ARG_BACKLIGHT,
+    ARG_DDC,
+    ARG_KEYBOARD_BACKLIGHT,
+    ARG_PLAYER,
+    ARG_DEVICE,
+    ARG_MAX_VOLUME,
+    ARG_VOLUME_CURVE,
+    ARG_BRIGHTNESS_CURVE,
+    ARG_PERCEIVED_PROGRESS,
+    ARG_CONFIG,
+    ARG_DAEMON,
+    ARG_NAMESPACE,
 ];
 
 pub trait Cli
 where
     Self: Sized,
 {
-    fn from_args() -> Option<Self>;
+    /// Builds the controller from the command line and the config file.
+    /// Returns `None` if no action is given, so that the usage gets shown.
+    fn from_args() -> Result<Option<Self>, String>;
+    /// Sets the option named `option` (without the leading `--`) from its
+    /// textual value. Returns `None` if the option is unknown or the value
+    /// is invalid.
+    fn set_option(&mut self, option: &str, value: &str) -> Option<()>;
+    /// Returns every option that still applies once the window exists, as
+    /// accepted by `set_option`. They are all sent, so that the receiver
+    /// shows what this instance resolved from its own config and arguments.
+    fn overrides(&self) -> Vec<(String, String)>;
     fn print_usage();
 }
 
 impl Cli for MediaController {
-    fn from_args() -> Option<Self> {
+    fn from_args() -> Result<Option<Self>, String> {
         let args = std::env::args().skip(1).collect::<Vec<_>>();
-        let mut controller = Self::default();
+        let mut options = Vec::new();
         let mut action_i = 0;
         for arg in &args {
             if !arg.starts_with("--") {
                 break;
             }
             action_i += 1;
-            let (option, value) = arg.split_once('=')?;
-            let option = &option[2..];
-            match option {
-                ARG_WIDTH | ARG_HEIGHT | ARG_BOTTOM => {
-                    if let Ok(parsed) = value.parse::<u32>() {
-                        match option {
-                            ARG_WIDTH => controller.width = parsed,
-                            ARG_HEIGHT => controller.height = parsed,
-                            ARG_BOTTOM => controller.bottom = parsed,
-                            _ => panic!(),
-                        }
-                        continue;
+            // Boolean options can be given without a value.
+            let (option, value) = arg.split_once('=').unwrap_or((arg, "true"));
+            options.push((&option[2..], value));
+        }
+        let daemon = match options.iter().find(|(option, _)| *option == ARG_DAEMON) {
+            Some((_, value)) => value
+                .parse::<bool>()
+                .map_err(|_| format!("invalid option --{ARG_DAEMON}={value}"))?,
+            None => false,
+        };
+        // The daemon receives its actions from other instances.
+        let action = if daemon {
+            if action_i < args.len() {
+                return Err(format!(
+                    "--{ARG_DAEMON} doesn't take an action, send it from another instance"
+                ));
+            }
+            Action::default()
+        } else if action_i == args.len() {
+            return Ok(None);
+        } else {
+            parse_action(&args[action_i..])?
+        };
+        let mut controller = Self {
+            action,
+            daemon,
+            ..Self::default()
+        };
+
+        // Options given on the command line take precedence over the config file.
+        let config_path = match options.iter().find(|(option, _)| *option == ARG_CONFIG) {
+            Some((_, path)) => Some(std::path::PathBuf::from(path.trim_matches('"'))),
+            None => Config::default_path().filter(|path| path.exists()),
+        };
+        let config = config_path.map(|path| Config::load(&path)).transpose()?;
+        match &config {
+            // The options of each kind of action come along with the
+            // commands.
+            Some(config) if daemon => config.apply_global(&mut controller),
+            Some(config) => config.apply(&mut controller),
+            None => {}
+        }
+        for (option, value) in options {
+            if option != ARG_CONFIG && option != ARG_DAEMON {
+                controller
+                    .set_option(option, value)
+                    .ok_or_else(|| format!("invalid option --{option}={value}"))?;
+            }
+        }
+        // Resolved here so that a daemon gets the actual device name.
+        if let Some(device) = config
+            .as_ref()
+            .zip(controller.device.as_deref())
+            .and_then(|(config, device)| config.device_alias(device))
+        {
+            controller.device = Some(device.to_string());
+        }
+        Ok(Some(controller))
+    }
+
+    fn set_option(&mut self, option: &str, value: &str) -> Option<()> {
+        match option {
+            ARG_WIDTH | ARG_HEIGHT | ARG_TOP | ARG_BOTTOM | ARG_LEFT | ARG_RIGHT => {
+                if let Ok(parsed) = value.parse::<u32>() {
+                    match option {
+                        ARG_WIDTH => self.width = parsed,
+                        ARG_HEIGHT => self.height = parsed,
+                        ARG_TOP => self.top = parsed,
+                        ARG_BOTTOM => self.bottom = parsed,
+                        ARG_LEFT => self.left = parsed,
+                        ARG_RIGHT => self.right = parsed,
+                        _ => panic!(),
                     }
+                    return Some(());
                 }
-                ARG_FONT_DESCRIPTION => {
-                    controller.font_description = value.trim_matches('"').to_string();
-                    continue;
+            }
+            ARG_MONITOR => {
+                if let Some(parsed) = Monitor::parse(value.trim_matches('"')) {
+                    self.monitor = parsed;
+                    return Some(());
+                }
+            }
+            ARG_ANCHOR => {
+                if let Some(parsed) = Anchor::parse(value.trim_matches('"')) {
+                    self.anchor = parsed;
+                    return Some(());
                 }
-                ARG_COLOR => {
-                    if let Some(parsed) = Color::from_hex(value.trim_matches('"')) {
-                        controller.color = parsed;
-                        continue;
+            }
+            ARG_FONT_DESCRIPTION => {
+                self.font_description = value.trim_matches('"').to_string();
+                return Some(());
+            }
+            ARG_COLOR | ARG_BAR_FILL_COLOR | ARG_BAR_TRACK_COLOR | ARG_BAR_BORDER_COLOR
+            | ARG_BAR_OVER_COLOR => {
+                if let Some(parsed) = Color::from_hex(value.trim_matches('"')) {
+                    match option {
+                        ARG_COLOR => self.color = parsed,
+                        ARG_BAR_FILL_COLOR => self.bar_fill_color = parsed,
+                        ARG_BAR_TRACK_COLOR => self.bar_track_color = parsed,
+                        ARG_BAR_BORDER_COLOR => self.bar_border_color = parsed,
+                        ARG_BAR_OVER_COLOR => self.bar_over_color = parsed,
+                        _ => panic!(),
                     }
+                    return Some(());
                 }
-                ARG_DURATION => {
-                    if let Ok(parsed) = value.parse::<f32>() {
-                        controller.duration = parsed;
-                        continue;
+            }
+            ARG_DURATION | ARG_SHOW_DURATION | ARG_HIDE_DURATION => match value.parse::<f32>() {
+                Ok(parsed) if parsed.is_finite() && parsed >= 0.0 => {
+                    match option {
+                        ARG_DURATION => self.duration = parsed,
+                        ARG_SHOW_DURATION => self.show_duration = parsed,
+                        ARG_HIDE_DURATION => self.hide_duration = parsed,
+                        _ => panic!(),
                     }
+                    return Some(());
                 }
-                ARG_FILLED => {
-                    if let Ok(parsed) = value.parse::<char>() {
-                        controller.filled = parsed;
-                        continue;
+                _ => {}
+            },
+            ARG_SHOW_ANIMATION | ARG_HIDE_ANIMATION => {
+                if let Some(parsed) = WindowAnimation::parse(value.trim_matches('"')) {
+                    match option {
+                        ARG_SHOW_ANIMATION => self.show_animation = parsed,
+                        ARG_HIDE_ANIMATION => self.hide_animation = parsed,
+                        _ => panic!(),
                     }
+                    return Some(());
+                }
+            }
+            ARG_FILLED => {
+                if let Ok(parsed) = value.parse::<char>() {
+                    self.filled = parsed;
+                    return Some(());
                 }
-                ARG_HALF_FILLED => {
-                    if let Ok(parsed) = value.parse::<char>() {
-                        controller.half_filled = parsed;
-                        continue;
+            }
+            ARG_HALF_FILLED => {
+                if let Ok(parsed) = value.parse::<char>() {
+                    self.half_filled = parsed;
+                    return Some(());
+                }
+            }
+            ARG_EMPTY => {
+                if let Ok(parsed) = value.parse::<char>() {
+                    self.empty = parsed;
+                    return Some(());
+                }
+            }
+            ARG_OVER_FILLED => {
+                if let Ok(parsed) = value.parse::<char>() {
+                    self.over_filled = parsed;
+                    return Some(());
+                }
+            }
+            ARG_GRAPHIC_BAR => {
+                if let Ok(parsed) = value.parse::<bool>() {
+                    self.graphic_bar = parsed;
+                    return Some(());
+                }
+            }
+            ARG_TRANSITION_DURATION => match value.parse::<f32>() {
+                Ok(parsed) if parsed.is_finite() && parsed >= 0.0 => {
+                    self.transition_duration = parsed;
+                    return Some(());
+                }
+                _ => {}
+            },
+            ARG_TRANSITION_EASING => {
+                if let Some(parsed) = Easing::parse(value.trim_matches('"')) {
+                    self.transition_easing = parsed;
+                    return Some(());
+                }
+            }
+            ARG_ICON | ARG_MUTED_ICON => {
+                if let Some(parsed) = Icon::parse(value.trim_matches('"')) {
+                    match option {
+                        ARG_ICON => self.icon = parsed,
+                        ARG_MUTED_ICON => self.muted_icon = parsed,
+                        _ => panic!(),
                     }
+                    return Some(());
                 }
-                ARG_EMPTY => {
-                    if let Ok(parsed) = value.parse::<char>() {
-                        controller.empty = parsed;
-                        continue;
+            }
+            ARG_BACKLIGHT => {
+                let value = value.trim_matches('"');
+                self.backlight = (!value.is_empty()).then(|| value.to_string());
+                return Some(());
+            }
+            ARG_KEYBOARD_BACKLIGHT => {
+                let value = value.trim_matches('"');
+                self.keyboard_backlight = (!value.is_empty()).then(|| value.to_string());
+                return Some(());
+            }
+            ARG_DDC => {
+                self.ddc = value
+                    .trim_matches('"')
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|output| !output.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                return Some(());
+            }
+            ARG_PLAYER => {
+                let value = value.trim_matches('"');
+                self.player = (!value.is_empty()).then(|| value.to_string());
+                return Some(());
+            }
+            ARG_DEVICE => {
+                let value = value.trim_matches('"');
+                self.device = (!value.is_empty()).then(|| value.to_string());
+                return Some(());
+            }
+            ARG_MAX_VOLUME => {
+                if let Ok(parsed @ 100..) = value.parse::<u8>() {
+                    self.max_volume = parsed;
+                    return Some(());
+                }
+            }
+            ARG_VOLUME_CURVE | ARG_BRIGHTNESS_CURVE => {
+                if let Some(parsed) = Curve::parse(value.trim_matches('"')) {
+                    match option {
+                        ARG_VOLUME_CURVE => self.volume_curve = parsed,
+                        ARG_BRIGHTNESS_CURVE => self.brightness_curve = parsed,
+                        _ => panic!(),
                     }
+                    return Some(());
                 }
-                _ => {}
             }
-            return None;
+            ARG_PERCEIVED_PROGRESS => {
+                if let Ok(parsed) = value.parse::<bool>() {
+                    self.perceived_progress = parsed;
+                    return Some(());
+                }
+            }
+            ARG_NAMESPACE => {
+                let value = value.trim_matches('"');
+                if !value.is_empty() {
+                    self.namespace = Some(value.to_string());
+                    return Some(());
+                }
+            }
+            _ => {}
         }
-        let action_args = &args[action_i..];
-        controller.action = match action_args.len() {
-            2 => {
-                if action_args[0] == "v" && action_args[1] == "mute" {
-                    Some(Action::VolumeToggleMute)
-                } else if action_args[0] == "m" && action_args[1] == "mute" {
-                    Some(Action::MicrophoneToggleMute)
-                } else {
-                    None
-                }
-            }
-            3 => {
-                let parsed = action_args[2].parse::<u8>().ok()?;
-                match (action_args[0].as_str(), action_args[1].as_str()) {
-                    ("v", "up") => Some(Action::VolumeUp(parsed)),
-                    ("v", "down") => Some(Action::VolumeDown(parsed)),
-                    ("b", "up") => Some(Action::BrightnessUp(parsed)),
-                    ("b", "down") => Some(Action::BrightnessDown(parsed)),
-                    _ => None,
-                }
-            }
-            _ => None,
-        }?;
-        Some(controller)
+        None
+    }
+
+    fn overrides(&self) -> Vec<(String, String)> {
+        let mut overrides = Vec::new();
+        let mut push = |option: &str, value: String| overrides.push((option.to_string(), value));
+        push(ARG_COLOR, self.color.to_string());
+        push(ARG_FONT_DESCRIPTION, self.font_description.clone());
+        push(ARG_FILLED, self.filled.to_string());
+        push(ARG_HALF_FILLED, self.half_filled.to_string());
+        push(ARG_EMPTY, self.empty.to_string());
+        push(ARG_OVER_FILLED, self.over_filled.to_string());
+        push(ARG_GRAPHIC_BAR, self.graphic_bar.to_string());
+        push(ARG_BAR_FILL_COLOR, self.bar_fill_color.to_string());
+        push(ARG_BAR_TRACK_COLOR, self.bar_track_color.to_string());
+        push(ARG_BAR_BORDER_COLOR, self.bar_border_color.to_string());
+        push(ARG_BAR_OVER_COLOR, self.bar_over_color.to_string());
+        push(
+            ARG_TRANSITION_DURATION,
+            self.transition_duration.to_string(),
+        );
+        push(ARG_TRANSITION_EASING, self.transition_easing.to_string());
+        push(ARG_ICON, self.icon.to_string());
+        push(ARG_MUTED_ICON, self.muted_icon.to_string());
+        push(ARG_BACKLIGHT, self.backlight.clone().unwrap_or_default());
+        push(ARG_DDC, self.ddc.join(","));
+        push(
+            ARG_KEYBOARD_BACKLIGHT,
+            self.keyboard_backlight.clone().unwrap_or_default(),
+        );
+        push(ARG_PLAYER, self.player.clone().unwrap_or_default());
+        push(ARG_DEVICE, self.device.clone().unwrap_or_default());
+        push(ARG_MAX_VOLUME, self.max_volume.to_string());
+        push(ARG_VOLUME_CURVE, self.volume_curve.to_string());
+        push(ARG_BRIGHTNESS_CURVE, self.brightness_curve.to_string());
+        push(ARG_PERCEIVED_PROGRESS, self.perceived_progress.to_string());
+        overrides
     }
 
     fn print_usage() {
@@ -130,11 +408,18 @@ impl Cli for MediaController {
         println!("{NAME} v{VERSION}");
         println!("{AUTHORS}");
         println!("\nUSAGE:");
-        println!("{TAB}{NAME} [OPTIONS] v up|down|mute {{number}}");
-        println!("{TAB}{NAME} [OPTIONS] m mute");
-        println!("{TAB}{NAME} [OPTIONS] b up|down {{number}}");
+        println!("{TAB}{NAME} [OPTIONS] v up|down|set {{number}}");
+        println!("{TAB}{NAME} [OPTIONS] v mute [on|off]");
+        println!("{TAB}{NAME} [OPTIONS] v next-device");
+        println!("{TAB}{NAME} [OPTIONS] m up|down|set {{number}}");
+        println!("{TAB}{NAME} [OPTIONS] m mute [on|off]");
+        println!("{TAB}{NAME} [OPTIONS] b up|down|set {{number}}");
+        println!("{TAB}{NAME} [OPTIONS] k up|down|set {{number}}");
+        println!("{TAB}{NAME} [OPTIONS] k toggle");
+        println!("{TAB}{NAME} [OPTIONS] p play-pause|next|previous");
+        println!("{TAB}{NAME} [OPTIONS] --{ARG_DAEMON}");
         println!("\nOPTIONS:");
-        println!("Format --{{option}}={{value}}");
+        println!("Format --{{option}}={{value}}, or --{{option}} for true/false options");
 
         let biggest_arg_len = ARGS
             .iter()
@@ -148,6 +433,26 @@ impl Cli for MediaController {
             pad(ARG_DURATION),
             default_controller.duration
         );
+        println!(
+            "{TAB}{}{TAB}Duration in seconds of the animation showing the window, 0 to show it at once. {def_str}{}",
+            pad(ARG_SHOW_DURATION),
+            default_controller.show_duration
+        );
+        println!(
+            "{TAB}{}{TAB}Animation showing the window (none, fade or slide). {def_str}{}",
+            pad(ARG_SHOW_ANIMATION),
+            default_controller.show_animation
+        );
+        println!(
+            "{TAB}{}{TAB}Duration in seconds of the animation hiding the window once the lifespan runs out, 0 to hide it at once. {def_str}{}",
+            pad(ARG_HIDE_DURATION),
+            default_controller.hide_duration
+        );
+        println!(
+            "{TAB}{}{TAB}Animation hiding the window, same values as --{ARG_SHOW_ANIMATION}. {def_str}{}",
+            pad(ARG_HIDE_ANIMATION),
+            default_controller.hide_animation
+        );
         println!(
             "{TAB}{}{TAB}Width of the window in px. {def_str}{}",
             pad(ARG_WIDTH),
@@ -159,10 +464,35 @@ impl Cli for MediaController {
             default_controller.height
         );
         println!(
-            "{TAB}{}{TAB}Offset from the bottom of the screen in px. {def_str}{}",
+            "{TAB}{}{TAB}Monitor to show the window on (focused, primary, an index from 0 or a connector name like DP-1). {def_str}{}",
+            pad(ARG_MONITOR),
+            default_controller.monitor
+        );
+        println!(
+            "{TAB}{}{TAB}Where to place the window (top, bottom, left, right, top-left, top-right, bottom-left, bottom-right or center). {def_str}{}",
+            pad(ARG_ANCHOR),
+            default_controller.anchor
+        );
+        println!(
+            "{TAB}{}{TAB}Space between the window and the top of the screen in px, if placed against it. {def_str}{}",
+            pad(ARG_TOP),
+            default_controller.top
+        );
+        println!(
+            "{TAB}{}{TAB}Space between the window and the bottom of the screen in px, if placed against it. {def_str}{}",
             pad(ARG_BOTTOM),
             default_controller.bottom
         );
+        println!(
+            "{TAB}{}{TAB}Space between the window and the left of the screen in px, if placed against it. {def_str}{}",
+            pad(ARG_LEFT),
+            default_controller.left
+        );
+        println!(
+            "{TAB}{}{TAB}Space between the window and the right of the screen in px, if placed against it. {def_str}{}",
+            pad(ARG_RIGHT),
+            default_controller.right
+        );
         println!(
             "{TAB}{}{TAB}Color of the window in hex (#RRGGBB or #RRGGBBAA). {def_str}\"{}\"",
             pad(ARG_COLOR),
@@ -188,6 +518,283 @@ impl Cli for MediaController {
             pad(ARG_EMPTY),
             default_controller.empty
         );
+        println!(
+            "{TAB}{}{TAB}Filled character used in the progress bar past 100% volume. {def_str}\"{}\"",
+            pad(ARG_OVER_FILLED),
+            default_controller.over_filled
+        );
+        println!(
+            "{TAB}{}{TAB}Draw the progress bar instead of using characters. {def_str}{}",
+            pad(ARG_GRAPHIC_BAR),
+            default_controller.graphic_bar
+        );
+        println!(
+            "{TAB}{}{TAB}Fill color of the drawn progress bar. {def_str}\"{}\"",
+            pad(ARG_BAR_FILL_COLOR),
+            default_controller.bar_fill_color
+        );
+        println!(
+            "{TAB}{}{TAB}Track color of the drawn progress bar. {def_str}\"{}\"",
+            pad(ARG_BAR_TRACK_COLOR),
+            default_controller.bar_track_color
+        );
+        println!(
+            "{TAB}{}{TAB}Border color of the drawn progress bar. {def_str}\"{}\"",
+            pad(ARG_BAR_BORDER_COLOR),
+            default_controller.bar_border_color
+        );
+        println!(
+            "{TAB}{}{TAB}Fill color of the drawn progress bar past 100% volume. {def_str}\"{}\"",
+            pad(ARG_BAR_OVER_COLOR),
+            default_controller.bar_over_color
+        );
+        println!(
+            "{TAB}{}{TAB}Duration in seconds of the progress bar's transitions, 0 to disable them. {def_str}{}",
+            pad(ARG_TRANSITION_DURATION),
+            default_controller.transition_duration
+        );
+        println!(
+            "{TAB}{}{TAB}Easing of the progress bar's transitions (linear, ease-in, ease-out or ease-in-out). {def_str}{}",
+            pad(ARG_TRANSITION_EASING),
+            default_controller.transition_easing
+        );
+        println!(
+            "{TAB}{}{TAB}Icon shown next to the label (auto, none, an icon name or an image path). {def_str}{}",
+            pad(ARG_ICON),
+            default_controller.icon
+        );
+        println!(
+            "{TAB}{}{TAB}Icon shown while muted, same values as --{ARG_ICON}. {def_str}{}",
+            pad(ARG_MUTED_ICON),
+            default_controller.muted_icon
+        );
+        println!(
+            "{TAB}{}{TAB}Backlight device in /sys/class/backlight to control. {def_str}{}",
+            pad(ARG_BACKLIGHT),
+            default_controller.backlight.as_deref().unwrap_or("auto")
+        );
+        println!(
+            "{TAB}{}{TAB}Comma-separated outputs (e.g. DP-1 or /dev/i2c-5) whose brightness is set over DDC/CI instead. {def_str}{}",
+            pad(ARG_DDC),
+            if default_controller.ddc.is_empty() {
+                "none".to_string()
+            } else {
+                default_controller.ddc.join(",")
+            }
+        );
+        println!(
+            "{TAB}{}{TAB}Keyboard backlight device in /sys/class/leds to control. {def_str}{}",
+            pad(ARG_KEYBOARD_BACKLIGHT),
+            default_controller
+                .keyboard_backlight
+                .as_deref()
+                .unwrap_or("auto")
+        );
+        println!(
+            "{TAB}{}{TAB}MPRIS player to control, e.g. spotify. {def_str}{}",
+            pad(ARG_PLAYER),
+            default_controller.player.as_deref().unwrap_or("auto")
+        );
+        println!(
+            "{TAB}{}{TAB}Sink, or source for microphone actions, to control instead of the default one, by name or alias. {def_str}{}",
+            pad(ARG_DEVICE),
+            default_controller.device.as_deref().unwrap_or("none")
+        );
+        println!(
+            "{TAB}{}{TAB}Highest volume in %, above 100 to allow boosting (up to 255). {def_str}{}",
+            pad(ARG_MAX_VOLUME),
+            default_controller.max_volume
+        );
+        println!(
+            "{TAB}{}{TAB}Volume curve (linear, exponential or a gamma value like 2.2). {def_str}{}",
+            pad(ARG_VOLUME_CURVE),
+            default_controller.volume_curve
+        );
+        println!(
+            "{TAB}{}{TAB}Brightness curve (linear, exponential or a gamma value like 2.2). {def_str}{}",
+            pad(ARG_BRIGHTNESS_CURVE),
+            default_controller.brightness_curve
+        );
+        println!(
+            "{TAB}{}{TAB}Show the perceived value in the progress bar instead of the raw one. {def_str}{}",
+            pad(ARG_PERCEIVED_PROGRESS),
+            default_controller.perceived_progress
+        );
+        println!(
+            "{TAB}{}{TAB}Path of the config file. {def_str}\"{}\"",
+            pad(ARG_CONFIG),
+            Config::default_path()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default()
+        );
+        println!(
+            "{TAB}{}{TAB}Stay resident and perform the actions sent by other instances. {def_str}{}",
+            pad(ARG_DAEMON),
+            default_controller.daemon
+        );
+        println!(
+            "{TAB}{}{TAB}Separate instances per seat, display, or any other name. {def_str}{}",
+            pad(ARG_NAMESPACE),
+            default_controller.namespace.as_deref().unwrap_or("none")
+        );
         println!("\n");
     }
 }
+
+/// Parses the positional arguments describing the action, e.g. `v up 5`.
+pub fn parse_action(action_args: &[String]) -> Result<Action, String> {
+    let invalid = || format!("invalid action {:?}", action_args.join(" "));
+    match action_args.len() {
+        2 => match (action_args[0].as_str(), action_args[1].as_str()) {
+            ("v", "mute") => Ok(Action::VolumeToggleMute),
+            ("v", "next-device") => Ok(Action::VolumeNextDevice),
+            ("m", "mute") => Ok(Action::MicrophoneToggleMute),
+            ("p", "play-pause") => Ok(Action::PlayPause),
+            ("p", "next") => Ok(Action::NextTrack),
+            ("p", "previous") => Ok(Action::PreviousTrack),
+            ("k", "toggle") => Ok(Action::KeyboardToggle),
+            _ => Err(invalid()),
+        },
+        3 => {
+            let value = action_args[2].as_str();
+            if action_args[1] == "mute" {
+                let mute = match value {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(invalid()),
+                };
+                return match action_args[0].as_str() {
+                    "v" => Ok(Action::VolumeSetMute(mute)),
+                    "m" => Ok(Action::MicrophoneSetMute(mute)),
+                    _ => Err(invalid()),
+                };
+            }
+            match (action_args[0].as_str(), action_args[1].as_str()) {
+                ("v", "up") => Ok(Action::VolumeUp(parse_step(value)?)),
+                ("v", "down") => Ok(Action::VolumeDown(parse_step(value)?)),
+                // Capped by `max_volume` when performed.
+                ("v", "set") => Ok(Action::VolumeSet(parse_level(value, u8::MAX)?)),
+                ("m", "up") => Ok(Action::MicrophoneUp(parse_step(value)?)),
+                ("m", "down") => Ok(Action::MicrophoneDown(parse_step(value)?)),
+                ("m", "set") => Ok(Action::MicrophoneSet(parse_level(value, 100)?)),
+                ("b", "up") => Ok(Action::BrightnessUp(parse_step(value)?)),
+                ("b", "down") => Ok(Action::BrightnessDown(parse_step(value)?)),
+                ("b", "set") => Ok(Action::BrightnessSet(parse_level(value, 100)?)),
+                ("k", "up") => Ok(Action::KeyboardUp(parse_step(value)?)),
+                ("k", "down") => Ok(Action::KeyboardDown(parse_step(value)?)),
+                ("k", "set") => Ok(Action::KeyboardSet(parse_level(value, 100)?)),
+                _ => Err(invalid()),
+            }
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Steps can be fractional, e.g. `0.5`, for mixers with a finer resolution
+/// than 1%.
+fn parse_step(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(step) if step > 0.0 && step <= 100.0 => Ok(step),
+        _ => Err(format!(
+            "invalid step {value:?}, expected a number greater than 0 and up to 100"
+        )),
+    }
+}
+
+fn parse_level(value: &str, max: u8) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(level) if (0.0..=max as f32).contains(&level) => Ok(level),
+        _ => Err(format!(
+            "invalid value {value:?}, expected a number from 0 to {max}"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(args: &str) -> Result<Action, String> {
+        let args = args.split(' ').map(str::to_string).collect::<Vec<_>>();
+        parse_action(&args)
+    }
+
+    #[test]
+    fn parses_actions() {
+        assert_eq!(action("v mute"), Ok(Action::VolumeToggleMute));
+        assert_eq!(action("v mute on"), Ok(Action::VolumeSetMute(true)));
+        assert_eq!(action("m mute off"), Ok(Action::MicrophoneSetMute(false)));
+        assert_eq!(action("v next-device"), Ok(Action::VolumeNextDevice));
+        assert_eq!(action("p play-pause"), Ok(Action::PlayPause));
+        assert_eq!(action("k toggle"), Ok(Action::KeyboardToggle));
+        assert_eq!(action("v up 0.5"), Ok(Action::VolumeUp(0.5)));
+        assert_eq!(action("b down 100"), Ok(Action::BrightnessDown(100.0)));
+        assert_eq!(action("v set 150"), Ok(Action::VolumeSet(150.0)));
+        assert_eq!(action("k set 0"), Ok(Action::KeyboardSet(0.0)));
+    }
+
+    #[test]
+    fn rejects_invalid_actions() {
+        for args in [
+            "v",
+            "v sideways",
+            "b mute",
+            "b mute on",
+            "v mute maybe",
+            "p next 5",
+            "x up 5",
+            "v up 5 6",
+        ] {
+            assert_eq!(action(args), Err(format!("invalid action {args:?}")));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_steps() {
+        for step in ["0", "-5", "100.5", "200", "inf", "NaN", "five"] {
+            assert_eq!(
+                action(&format!("v up {step}")),
+                Err(format!(
+                    "invalid step {step:?}, expected a number greater than 0 and up to 100"
+                ))
+            );
+        }
+        assert_eq!(parse_step("100"), Ok(100.0));
+    }
+
+    #[test]
+    fn rejects_invalid_levels() {
+        for (args, value, max) in [
+            ("b set 101", "101", 100),
+            ("m set -1", "-1", 100),
+            ("v set 256", "256", 255),
+            ("k set inf", "inf", 100),
+            ("b set NaN", "NaN", 100),
+        ] {
+            assert_eq!(
+                action(args),
+                Err(format!(
+                    "invalid value {value:?}, expected a number from 0 to {max}"
+                ))
+            );
+        }
+        assert_eq!(parse_level("255", u8::MAX), Ok(255.0));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        let mut controller = MediaController::default();
+        for option in [ARG_DURATION, ARG_SHOW_DURATION, ARG_HIDE_DURATION] {
+            for value in ["inf", "NaN", "-1", "soon"] {
+                assert_eq!(
+                    controller.set_option(option, value),
+                    None,
+                    "{option}={value}"
+                );
+            }
+            assert_eq!(controller.set_option(option, "0"), Some(()));
+        }
+        controller.set_option(ARG_DURATION, "2.5").unwrap();
+        assert_eq!(controller.duration, 2.5);
+    }
+}
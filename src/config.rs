@@ -0,0 +1,353 @@
+//! Config file support.
+//!
+//! The config file uses a subset of TOML: `key = value` pairs, optionally
+//! grouped under `[section]` headers. Keys are the same as the command line
+//! options. Pairs before the first section apply to every action, the ones
+//! under `[volume]`, `[microphone]`, `[brightness]`, `[keyboard]` or `[media]`
+//! only apply to the actions of that kind and take precedence.
+//!
+//! The `[devices]` section maps aliases to sink or source names, so that
+//! `--device` can be given the alias instead.
+//!
+//! Values are strings, numbers or booleans. Arrays and tables aren't
+//! supported, lists such as `ddc` are comma-separated strings instead.
+//!
+//! ```toml
+//! color = "#000000aa"
+//! font-description = "BigBlueTerm437 Nerd Font Mono"
+//!
+//! [microphone]
+//! color = "#aa0000aa"
+//!
+//! [devices]
+//! headphones = "alsa_output.usb-Generic_USB_Audio-00.analog-stereo"
+//! hdmi = "alsa_output.pci-0000_01_00.1.hdmi-stereo"
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use crate::cli::{Cli, NAME};
+use crate::MediaController;
+
+/// Section mapping aliases to device names.
+const DEVICES_SECTION: &str = "devices";
+/// Sections of the options for each kind of action.
+const ACTION_SECTIONS: &[&str] = &["volume", "microphone", "brightness", "keyboard", "media"];
+
+struct Entry {
+    section: Option<String>,
+    key: String,
+    value: String,
+    line: usize,
+}
+
+pub struct Config {
+    path: PathBuf,
+    entries: Vec<Entry>,
+}
+impl Config {
+    /// `$XDG_CONFIG_HOME/media-controller/config.toml`, falling back to
+    /// `~/.config` when `$XDG_CONFIG_HOME` isn't set.
+    pub fn default_path() -> Option<PathBuf> {
+        let config_home = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => Path::new(&std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_home.join(NAME).join("config.toml"))
+    }
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+        let entries = Self::parse(&content).map_err(|err| format!("{}:{err}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+    fn parse(content: &str) -> Result<Vec<Entry>, String> {
+        let mut entries = Vec::new();
+        let mut section = None;
+        for (i, line) in content.lines().enumerate() {
+            let line_number = i + 1;
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[') {
+                let name = name
+                    .strip_suffix(']')
+                    .ok_or_else(|| format!("{line_number}: unterminated section header"))?;
+                let name = name.trim();
+                if name != DEVICES_SECTION && !ACTION_SECTIONS.contains(&name) {
+                    return Err(format!("{line_number}: unknown section [{name}]"));
+                }
+                section = Some(name.to_string());
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("{line_number}: expected `key = value`"))?;
+            let key = key.trim().trim_matches('"').to_string();
+            if value.trim_start().starts_with(['[', '{']) {
+                return Err(format!(
+                    "{line_number}: arrays and tables aren't supported, lists are comma-separated strings"
+                ));
+            }
+            let value =
+                parse_value(value.trim()).ok_or_else(|| format!("{line_number}: invalid value"))?;
+            entries.push(Entry {
+                section: section.clone(),
+                key,
+                value,
+                line: line_number,
+            });
+        }
+        Ok(entries)
+    }
+    /// Sets the options found in the config file on `controller`, first the
+    /// global ones and then the ones for its action. Invalid options are
+    /// reported and skipped.
+    pub fn apply(&self, controller: &mut MediaController) {
+        let action_section = controller.action.config_section();
+        let global = self.entries.iter().filter(|e| e.section.is_none());
+        let action = self
+            .entries
+            .iter()
+            .filter(|e| e.section.as_deref() == Some(action_section));
+        self.apply_entries(global.chain(action), controller);
+    }
+    /// Sets only the global options, for an instance without an action.
+    pub fn apply_global(&self, controller: &mut MediaController) {
+        let global = self.entries.iter().filter(|e| e.section.is_none());
+        self.apply_entries(global, controller);
+    }
+    fn apply_entries<'a>(
+        &self,
+        entries: impl Iterator<Item = &'a Entry>,
+        controller: &mut MediaController,
+    ) {
+        for entry in entries {
+            if controller.set_option(&entry.key, &entry.value).is_none() {
+                eprintln!(
+                    "{NAME}: {}:{}: invalid option {} = {:?}",
+                    self.path.display(),
+                    entry.line,
+                    entry.key,
+                    entry.value
+                );
+            }
+        }
+    }
+    /// Returns the device name that `alias` stands for, if it's in the
+    /// `[devices]` section.
+    pub fn device_alias(&self, alias: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|e| e.section.as_deref() == Some(DEVICES_SECTION) && e.key == alias)
+            .map(|e| e.value.as_str())
+    }
+}
+
+/// Removes a trailing `#` comment, leaving `#` inside quotes (colors) alone.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some('"'), '\\') => escaped = true,
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Returns the value as it would be written on the command line.
+fn parse_value(value: &str) -> Option<String> {
+    if let Some(literal) = value.strip_prefix('\'') {
+        let literal = literal.strip_suffix('\'')?;
+        return (!literal.contains('\'')).then(|| literal.to_string());
+    }
+    let Some(basic) = value.strip_prefix('"') else {
+        // Numbers, booleans and bare words are taken as they are.
+        return (!value.is_empty() && !value.contains(char::is_whitespace))
+            .then(|| value.to_string());
+    };
+    let basic = basic.strip_suffix('"')?;
+    let mut unescaped = String::with_capacity(basic.len());
+    let mut chars = basic.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            // Closes the string early, e.g. `"a" "b"`.
+            '"' => return None,
+            '\\' => {}
+            _ => {
+                unescaped.push(c);
+                continue;
+            }
+        }
+        match chars.next()? {
+            'n' => unescaped.push('\n'),
+            't' => unescaped.push('\t'),
+            '"' => unescaped.push('"'),
+            '\\' => unescaped.push('\\'),
+            'u' => {
+                let code = chars.by_ref().take(4).collect::<String>();
+                if code.len() != 4 {
+                    return None;
+                }
+                unescaped.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+            }
+            _ => return None,
+        }
+    }
+    Some(unescaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Action, Color};
+
+    fn config(content: &str) -> Config {
+        Config {
+            path: PathBuf::from("config.toml"),
+            entries: Config::parse(content).unwrap(),
+        }
+    }
+    fn parse_err(content: &str) -> String {
+        Config::parse(content).err().unwrap()
+    }
+
+    #[test]
+    fn parses_sections_and_comments() {
+        let entries = Config::parse(
+            "# Comment\n\
+             color = \"#000000aa\" # A color, not a comment\n\
+             duration = 1.5\n\
+             \n\
+             [ volume ]\n\
+             graphic-bar = true\n\
+             \"icon\" = 'audio-#1'\n",
+        )
+        .unwrap();
+        let entries = entries
+            .iter()
+            .map(|e| {
+                (
+                    e.section.as_deref(),
+                    e.key.as_str(),
+                    e.value.as_str(),
+                    e.line,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            [
+                (None, "color", "#000000aa", 2),
+                (None, "duration", "1.5", 3),
+                (Some("volume"), "graphic-bar", "true", 6),
+                (Some("volume"), "icon", "audio-#1", 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn unescapes_basic_strings() {
+        assert_eq!(
+            parse_value(r#""a \"b\" \\ \t\u00e9""#).unwrap(),
+            "a \"b\" \\ \t\u{e9}"
+        );
+        assert_eq!(parse_value(r"'C:\no\escapes'").unwrap(), r"C:\no\escapes");
+        assert_eq!(parse_value(r#""\x""#), None);
+        assert_eq!(parse_value(r#""\u00""#), None);
+        assert_eq!(parse_value(r#""trailing\""#), None);
+        assert_eq!(parse_value(r#""a" "b""#), None);
+        assert_eq!(parse_value("'a' 'b'"), None);
+        assert_eq!(parse_value("two words"), None);
+        assert_eq!(parse_value("\"unterminated"), None);
+    }
+
+    #[test]
+    fn reports_errors_with_line_numbers() {
+        assert_eq!(
+            parse_err("color = \"#fff\"\n[volume\n"),
+            "2: unterminated section header"
+        );
+        assert_eq!(parse_err("\n\nwidth 300\n"), "3: expected `key = value`");
+        assert_eq!(parse_err("width = 3 00\n"), "1: invalid value");
+        assert_eq!(
+            parse_err("[video]\nwidth = 300\n"),
+            "1: unknown section [video]"
+        );
+        assert_eq!(parse_err("[[volume]]\n"), "1: unknown section [[volume]]");
+    }
+
+    #[test]
+    fn rejects_arrays_and_tables() {
+        let expected = "1: arrays and tables aren't supported, lists are comma-separated strings";
+        assert_eq!(parse_err("ddc = [\"DP-1\", \"DP-2\"]\n"), expected);
+        assert_eq!(parse_err("ddc = [\"DP-1\",\"DP-2\"]\n"), expected);
+        assert_eq!(parse_err("color = { r = 1 }\n"), expected);
+
+        let mut controller = MediaController::default();
+        config("ddc = \"DP-1, DP-2\"\n").apply(&mut controller);
+        assert_eq!(controller.ddc, ["DP-1", "DP-2"]);
+    }
+
+    #[test]
+    fn action_sections_take_precedence() {
+        let config = config(
+            "color = \"#000000\"\n\
+             width = 200\n\
+             [volume]\n\
+             color = \"#ff0000\"\n\
+             [brightness]\n\
+             width = 400\n",
+        );
+        let mut volume = MediaController {
+            action: Action::VolumeUp(5.0),
+            ..MediaController::default()
+        };
+        config.apply(&mut volume);
+        assert_eq!(volume.color, Color::from_hex("#ff0000").unwrap());
+        assert_eq!(volume.width, 200);
+
+        let mut brightness = MediaController {
+            action: Action::BrightnessUp(5.0),
+            ..MediaController::default()
+        };
+        config.apply(&mut brightness);
+        assert_eq!(brightness.color, Color::from_hex("#000000").unwrap());
+        assert_eq!(brightness.width, 400);
+
+        // A daemon without an action leaves them to the commands.
+        let mut daemon = MediaController {
+            daemon: true,
+            ..MediaController::default()
+        };
+        config.apply_global(&mut daemon);
+        assert_eq!(daemon.color, Color::from_hex("#000000").unwrap());
+        assert_eq!(daemon.width, 200);
+    }
+
+    #[test]
+    fn looks_up_device_aliases() {
+        let config = config(
+            "[devices]\n\
+             headphones = \"alsa_output.usb\"\n\
+             hdmi = \"alsa_output.hdmi\"\n\
+             headphones = \"alsa_output.usb-2\"\n\
+             [volume]\n\
+             speakers = \"not an alias\"\n",
+        );
+        assert_eq!(config.device_alias("headphones"), Some("alsa_output.usb-2"));
+        assert_eq!(config.device_alias("hdmi"), Some("alsa_output.hdmi"));
+        assert_eq!(config.device_alias("speakers"), None);
+    }
+}
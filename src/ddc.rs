@@ -0,0 +1,381 @@
+//! Brightness of external monitors over DDC/CI, through the i2c-dev interface.
+//!
+//! The monitor's brightness is the MCCS VCP feature `0x10`. It is read with a
+//! "Get VCP Feature" request and written with a "Set VCP Feature" one, sent to
+//! the display at i2c address `0x37`. The `i2c-dev` kernel module must be
+//! loaded and the user needs access to `/dev/i2c-*`.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::{BackendResult, MediaBackend, MediaController};
+
+pub const DRM_CLASS: &str = "/sys/class/drm";
+
+/// `I2C_SLAVE` from `linux/i2c-dev.h`.
+const I2C_SLAVE: libc::Ioctl = 0x0703;
+
+/// Address of the DDC/CI interface of the display.
+const DISPLAY_ADDRESS: u8 = 0x37;
+/// Address used by the host as the source of its messages.
+const HOST_ADDRESS: u8 = 0x51;
+/// Seed of the checksum of the replies, standing in for the host's address.
+const REPLY_CHECKSUM_SEED: u8 = 0x50;
+const LENGTH_FLAG: u8 = 0x80;
+
+const OPCODE_GET_VCP: u8 = 0x01;
+const OPCODE_GET_VCP_REPLY: u8 = 0x02;
+const OPCODE_SET_VCP: u8 = 0x03;
+const VCP_BRIGHTNESS: u8 = 0x10;
+
+const GET_VCP_REPLY_LENGTH: usize = 11;
+/// Displays need time to process a request before they can reply, and
+/// between consecutive requests.
+const REPLY_DELAY: Duration = Duration::from_millis(40);
+const SET_DELAY: Duration = Duration::from_millis(50);
+/// DDC/CI is flaky on some displays, failed reads are retried this many times.
+const ATTEMPTS: usize = 3;
+
+/// Controls the brightness of external monitors over DDC/CI.
+///
+/// Outputs are given by connector name, e.g. `DP-1` as found in
+/// `/sys/class/drm`, or by the path of their i2c-dev device, e.g.
+/// `/dev/i2c-5`. Changes apply to every output, while the brightness
+/// reported is the one of the first.
+pub struct DdcBackend {
+    root: PathBuf,
+    outputs: Vec<String>,
+}
+impl DdcBackend {
+    /// Looks up connectors in `/sys/class/drm`.
+    pub fn new() -> Self {
+        Self::with_root(DRM_CLASS)
+    }
+    /// Looks up connectors in `root`, which must have the same layout as
+    /// `/sys/class/drm`.
+    pub fn with_root(root: impl AsRef<Path>) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            outputs: Vec::new(),
+        }
+    }
+    /// Picks the outputs to control.
+    pub fn set_outputs(&mut self, outputs: Vec<String>) {
+        self.outputs = outputs;
+    }
+    /// Returns the connectors that have a DDC bus along with the path of its
+    /// i2c-dev device, sorted.
+    pub fn outputs(&self) -> BackendResult<Vec<(String, PathBuf)>> {
+        let entries = std::fs::read_dir(&self.root)
+            .map_err(|err| format!("failed to read {}: {err}", self.root.display()))?;
+        let mut outputs = entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                // Connectors are named after their card, e.g. `card0-DP-1`.
+                let (_, connector) = name.strip_prefix("card")?.split_once('-')?;
+                Some((connector.to_string(), Self::bus_of(&entry.path())?))
+            })
+            .collect::<Vec<_>>();
+        outputs.sort();
+        Ok(outputs)
+    }
+    /// HDMI, DVI and VGA connectors link to their bus as `ddc`, DisplayPort
+    /// ones have it as an `i2c-*` child.
+    fn bus_of(connector: &Path) -> Option<PathBuf> {
+        let bus = match std::fs::read_link(connector.join("ddc")) {
+            Ok(target) => target.file_name()?.to_os_string(),
+            Err(_) => std::fs::read_dir(connector)
+                .ok()?
+                .flatten()
+                .map(|entry| entry.file_name())
+                .find(|name| name.to_string_lossy().starts_with("i2c-"))?,
+        };
+        Some(Path::new("/dev").join(bus))
+    }
+    fn bus_path(&self, output: &str) -> BackendResult<PathBuf> {
+        if output.contains('/') {
+            return Ok(PathBuf::from(output));
+        }
+        let outputs = self.outputs()?;
+        outputs
+            .iter()
+            .find(|(connector, _)| connector == output)
+            .map(|(_, path)| path.clone())
+            .ok_or_else(|| {
+                let available = outputs
+                    .iter()
+                    .map(|(connector, _)| connector.as_str())
+                    .collect::<Vec<_>>();
+                format!(
+                    "output {output} not found or without DDC (available: {})",
+                    available.join(", ")
+                )
+                .into()
+            })
+    }
+    fn buses(&self) -> BackendResult<Vec<Bus>> {
+        if self.outputs.is_empty() {
+            return Err("no DDC/CI output selected".into());
+        }
+        self.outputs
+            .iter()
+            .map(|output| Bus::open(&self.bus_path(output)?))
+            .collect()
+    }
+    /// Sets every output to `value` computed from its current and maximum
+    /// brightness.
+    fn update(&mut self, value: impl Fn(u16, u16) -> u16) -> BackendResult<()> {
+        for mut bus in self.buses()? {
+            let (current, maximum) = bus.get_vcp(VCP_BRIGHTNESS)?;
+            bus.set_vcp(VCP_BRIGHTNESS, value(current, maximum).min(maximum))?;
+        }
+        Ok(())
+    }
+}
+impl std::default::Default for DdcBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl MediaBackend for DdcBackend {
+    fn configure(&mut self, controller: &MediaController) -> BackendResult<()> {
+        self.set_outputs(controller.ddc.clone());
+        Ok(())
+    }
+    fn get_brightness(&mut self) -> BackendResult<u8> {
+        let mut bus = self.buses()?.swap_remove(0);
+        let (current, maximum) = bus.get_vcp(VCP_BRIGHTNESS)?;
+        if maximum == 0 {
+            return Err(format!("{} reports a maximum brightness of 0", bus.path.display()).into());
+        }
+        let percentage = current.min(maximum) as f32 * 100.0 / maximum as f32;
+        Ok(percentage.round() as u8)
+    }
+    fn set_brightness(&mut self, brightness: f32) -> BackendResult<()> {
+        self.update(|_, maximum| {
+            (brightness.clamp(0.0, 100.0) * maximum as f32 / 100.0).round() as u16
+        })
+    }
+    fn inc_brightness(&mut self, inc: f32) -> BackendResult<()> {
+        self.update(|current, maximum| {
+            let delta = (inc.abs() * maximum as f32 / 100.0).round() as u16;
+            // Same as the backlight, small increments move at least one step.
+            let delta = delta.max((inc != 0.0) as u16);
+            if inc < 0.0 {
+                current.saturating_sub(delta)
+            } else {
+                current.saturating_add(delta)
+            }
+        })
+    }
+}
+
+/// An i2c-dev device addressed to the DDC/CI interface of a display, or
+/// anything else that reads and writes its packets.
+struct Bus<T = File> {
+    path: PathBuf,
+    io: T,
+}
+impl Bus {
+    fn open(path: &Path) -> BackendResult<Self> {
+        let failed = |err: std::io::Error| format!("{}: {err}", path.display());
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(failed)?;
+        // Anything but an i2c-dev device refuses the request, which keeps
+        // the packets from being written to arbitrary files.
+        // SAFETY: `I2C_SLAVE` takes the address by value and `file` is open.
+        let address = libc::c_ulong::from(DISPLAY_ADDRESS);
+        if unsafe { libc::ioctl(file.as_raw_fd(), I2C_SLAVE, address) } < 0 {
+            return Err(failed(std::io::Error::last_os_error()).into());
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            io: file,
+        })
+    }
+}
+impl<T: Read + Write> Bus<T> {
+    /// Sends `payload` with the framing and checksum of DDC/CI.
+    fn send(&mut self, payload: &[u8]) -> BackendResult<()> {
+        let mut packet = vec![HOST_ADDRESS, LENGTH_FLAG | payload.len() as u8];
+        packet.extend(payload);
+        packet.push(checksum(DISPLAY_ADDRESS << 1, &packet));
+        self.io
+            .write_all(&packet)
+            .map_err(|err| format!("{}: {err}", self.path.display()).into())
+    }
+    /// Returns the current and maximum values of the VCP feature `code`.
+    fn get_vcp(&mut self, code: u8) -> BackendResult<(u16, u16)> {
+        let mut last_err = None;
+        for _ in 0..ATTEMPTS {
+            self.send(&[OPCODE_GET_VCP, code])?;
+            std::thread::sleep(REPLY_DELAY);
+            let mut reply = [0; GET_VCP_REPLY_LENGTH];
+            self.io
+                .read_exact(&mut reply)
+                .map_err(|err| format!("{}: {err}", self.path.display()))?;
+            match parse_get_vcp_reply(&reply, code) {
+                Ok(values) => return Ok(values),
+                Err(err) => last_err = Some(err),
+            }
+            std::thread::sleep(SET_DELAY);
+        }
+        Err(format!("{}: {}", self.path.display(), last_err.unwrap_or_default()).into())
+    }
+    fn set_vcp(&mut self, code: u8, value: u16) -> BackendResult<()> {
+        let [high, low] = value.to_be_bytes();
+        self.send(&[OPCODE_SET_VCP, code, high, low])?;
+        std::thread::sleep(SET_DELAY);
+        Ok(())
+    }
+}
+
+fn checksum(seed: u8, bytes: &[u8]) -> u8 {
+    bytes.iter().fold(seed, |checksum, byte| checksum ^ byte)
+}
+
+/// The reply is the display's address, the length, the opcode, a result code,
+/// the feature code, its type, then the maximum and current values (big
+/// endian) and the checksum.
+fn parse_get_vcp_reply(reply: &[u8], code: u8) -> Result<(u16, u16), String> {
+    // Displays that aren't ready send a (shorter) null message instead.
+    if reply[1] == LENGTH_FLAG {
+        return Err("the display is busy".to_string());
+    }
+    let (body, expected) = reply.split_at(reply.len() - 1);
+    if checksum(REPLY_CHECKSUM_SEED, body) != expected[0] {
+        return Err("invalid checksum in the DDC/CI reply".to_string());
+    }
+    if body[2] != OPCODE_GET_VCP_REPLY || body[4] != code {
+        return Err(format!("unexpected DDC/CI reply {body:02x?}"));
+    }
+    if body[3] != 0 {
+        return Err(format!(
+            "the display doesn't support VCP feature {code:#04x}"
+        ));
+    }
+    let maximum = u16::from_be_bytes([body[6], body[7]]);
+    let current = u16::from_be_bytes([body[8], body[9]]);
+    Ok((current, maximum))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    /// Records the packets written to it and answers reads with `replies`,
+    /// padded to the requested length like an i2c read.
+    struct FakeDisplay {
+        written: Vec<Vec<u8>>,
+        replies: VecDeque<Vec<u8>>,
+    }
+    impl Read for FakeDisplay {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let reply = self.replies.pop_front().unwrap_or_default();
+            buf.fill(0);
+            buf[..reply.len()].copy_from_slice(&reply);
+            Ok(buf.len())
+        }
+    }
+    impl Write for FakeDisplay {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.written.push(buf.to_vec());
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn bus(replies: impl IntoIterator<Item = Vec<u8>>) -> Bus<FakeDisplay> {
+        Bus {
+            path: PathBuf::from("/dev/i2c-5"),
+            io: FakeDisplay {
+                written: Vec::new(),
+                replies: replies.into_iter().collect(),
+            },
+        }
+    }
+
+    fn reply(result: u8, code: u8, maximum: u16, current: u16) -> Vec<u8> {
+        let mut reply = vec![DISPLAY_ADDRESS << 1, LENGTH_FLAG | 8, OPCODE_GET_VCP_REPLY];
+        reply.extend([result, code, 0]);
+        reply.extend(maximum.to_be_bytes());
+        reply.extend(current.to_be_bytes());
+        reply.push(checksum(REPLY_CHECKSUM_SEED, &reply));
+        reply
+    }
+
+    const BUSY: [u8; 3] = [0x6e, 0x80, 0xbe];
+    const GET_BRIGHTNESS: [u8; 5] = [0x51, 0x82, 0x01, 0x10, 0xac];
+
+    #[test]
+    fn get_vcp_reads_the_values() {
+        let mut bus = bus([reply(0, VCP_BRIGHTNESS, 100, 40)]);
+        assert_eq!(bus.get_vcp(VCP_BRIGHTNESS).unwrap(), (40, 100));
+        assert_eq!(bus.io.written, [GET_BRIGHTNESS]);
+    }
+
+    #[test]
+    fn get_vcp_retries_busy_displays() {
+        let mut bus = bus([BUSY.to_vec(), reply(0, VCP_BRIGHTNESS, 255, 128)]);
+        assert_eq!(bus.get_vcp(VCP_BRIGHTNESS).unwrap(), (128, 255));
+        assert_eq!(bus.io.written, [GET_BRIGHTNESS; 2]);
+    }
+
+    #[test]
+    fn get_vcp_gives_up_after_every_attempt() {
+        let mut corrupt = reply(0, VCP_BRIGHTNESS, 100, 40);
+        *corrupt.last_mut().unwrap() ^= 1;
+        let mut bus = bus(vec![corrupt; ATTEMPTS]);
+        assert_eq!(
+            bus.get_vcp(VCP_BRIGHTNESS).unwrap_err().to_string(),
+            "/dev/i2c-5: invalid checksum in the DDC/CI reply"
+        );
+        assert_eq!(bus.io.written.len(), ATTEMPTS);
+    }
+
+    #[test]
+    fn set_vcp_writes_the_value() {
+        let mut bus = bus([]);
+        bus.set_vcp(VCP_BRIGHTNESS, 50).unwrap();
+        assert_eq!(bus.io.written, [[0x51, 0x84, 0x03, 0x10, 0x00, 0x32, 0x9a]]);
+    }
+
+    #[test]
+    fn parses_get_vcp_replies() {
+        assert_eq!(
+            parse_get_vcp_reply(&reply(0, VCP_BRIGHTNESS, 100, 40), VCP_BRIGHTNESS),
+            Ok((40, 100))
+        );
+        let mut busy = [0; GET_VCP_REPLY_LENGTH];
+        busy[..BUSY.len()].copy_from_slice(&BUSY);
+        assert_eq!(
+            parse_get_vcp_reply(&busy, VCP_BRIGHTNESS),
+            Err("the display is busy".to_string())
+        );
+        assert_eq!(
+            parse_get_vcp_reply(&reply(1, VCP_BRIGHTNESS, 0, 0), VCP_BRIGHTNESS),
+            Err("the display doesn't support VCP feature 0x10".to_string())
+        );
+        assert!(
+            parse_get_vcp_reply(&reply(0, 0x12, 100, 40), VCP_BRIGHTNESS)
+                .unwrap_err()
+                .starts_with("unexpected DDC/CI reply")
+        );
+    }
+
+    #[test]
+    fn open_refuses_other_devices() {
+        assert!(Bus::open(Path::new("/dev/null")).is_err());
+    }
+}
@@ -0,0 +1,381 @@
+//! Messages exchanged between instances over the UNIX socket.
+//!
+//! Every message is a big-endian `u32` byte length followed by that many
+//! bytes of UTF-8 text, with one `key=value` field per line:
+//!
+//! ```text
+//! version=1
+//! kind=update
+//! action=v up 5
+//! value=55
+//! muted=false
+//! style.color=#000000AA
+//! ```
+//!
+//! Playback actions carry `status`, `title` and `artist` instead of `value`
+//! and `muted`. Audio actions carry the description of the device in `device`
+//! when it was picked explicitly or changed.
+//!
+//! `version` must come first. It only changes along with the meaning of
+//! existing fields, and messages of another version are refused. Fields this
+//! version doesn't know about are ignored, so that newer instances can add
+//! fields without breaking older ones.
+
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+use crate::{Action, PlaybackStatus, Track};
+
+pub const VERSION: u32 = 1;
+
+/// Upper bound on the length of a message, so that a malformed length prefix
+/// can't make the listener allocate an absurd amount of memory.
+const MAX_LENGTH: u32 = 64 * 1024;
+
+const STYLE_PREFIX: &str = "style.";
+
+/// How long to wait for the other end to send or take a message. Messages are
+/// written right after connecting, so this only runs out on a stalled peer.
+pub const TIMEOUT: Duration = Duration::from_secs(1);
+/// How long to wait for the daemon's reply, which comes once the action is
+/// performed.
+pub const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Bounds the time spent reading from and writing to `stream`, so that a
+/// stalled peer can't block this instance.
+pub fn set_timeouts(stream: &UnixStream, read_timeout: Duration) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(read_timeout))?;
+    stream.set_write_timeout(Some(TIMEOUT))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageKind {
+    /// Asks the daemon to perform `action`.
+    Command,
+    /// Tells the window owner what to display after performing `action`.
+    Update,
+    /// The daemon's answer to a command.
+    Reply,
+}
+impl MessageKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Command => "command",
+            Self::Update => "update",
+            Self::Reply => "reply",
+        }
+    }
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "command" => Some(Self::Command),
+            "update" => Some(Self::Update),
+            "reply" => Some(Self::Reply),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub kind: MessageKind,
+    pub action: Option<Action>,
+    /// Level after performing the action (0-100).
+    pub value: Option<u8>,
+    pub muted: Option<bool>,
+    /// What the media player is playing, for playback actions.
+    pub track: Option<Track>,
+    /// Description of the audio device.
+    pub device: Option<String>,
+    /// Icon name or path to display.
+    pub icon: Option<String>,
+    /// Set when the backend failed.
+    pub error: Option<String>,
+    /// Options that override the ones of the receiving instance, by name.
+    pub style: Vec<(String, String)>,
+}
+impl Message {
+    pub fn new(kind: MessageKind) -> Self {
+        Self {
+            kind,
+            action: None,
+            value: None,
+            muted: None,
+            track: None,
+            device: None,
+            icon: None,
+            error: None,
+            style: Vec::new(),
+        }
+    }
+    fn encode(&self) -> String {
+        let mut fields = vec![
+            ("version".to_string(), VERSION.to_string()),
+            ("kind".to_string(), self.kind.as_str().to_string()),
+        ];
+        if let Some(action) = self.action {
+            fields.push(("action".to_string(), action.to_string()));
+        }
+        if let Some(value) = self.value {
+            fields.push(("value".to_string(), value.to_string()));
+        }
+        if let Some(muted) = self.muted {
+            fields.push(("muted".to_string(), muted.to_string()));
+        }
+        if let Some(track) = &self.track {
+            fields.push(("status".to_string(), track.status.to_string()));
+            if let Some(title) = &track.title {
+                fields.push(("title".to_string(), title.clone()));
+            }
+            if let Some(artist) = &track.artist {
+                fields.push(("artist".to_string(), artist.clone()));
+            }
+        }
+        if let Some(device) = &self.device {
+            fields.push(("device".to_string(), device.clone()));
+        }
+        if let Some(icon) = &self.icon {
+            fields.push(("icon".to_string(), icon.clone()));
+        }
+        if let Some(error) = &self.error {
+            fields.push(("error".to_string(), error.clone()));
+        }
+        for (option, value) in &self.style {
+            fields.push((format!("{STYLE_PREFIX}{option}"), value.clone()));
+        }
+        fields
+            .iter()
+            .map(|(key, value)| format!("{key}={}\n", escape(value)))
+            .collect()
+    }
+    fn decode(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        let version = lines
+            .next()
+            .and_then(|line| line.strip_prefix("version="))
+            .ok_or("missing version")?;
+        let version = version
+            .parse::<u32>()
+            .map_err(|_| format!("invalid version {version:?}"))?;
+        if version != VERSION {
+            return Err(format!("unsupported version {version}, expected {VERSION}"));
+        }
+
+        let mut kind = None;
+        let mut message = Self::new(MessageKind::Update);
+        for line in lines {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("invalid field {line:?}"))?;
+            let value = unescape(value);
+            let invalid = || format!("invalid {key} {value:?}");
+            match key {
+                "kind" => kind = Some(MessageKind::parse(&value).ok_or_else(invalid)?),
+                "action" => {
+                    let words = value
+                        .split_whitespace()
+                        .map(str::to_string)
+                        .collect::<Vec<_>>();
+                    message.action = Some(crate::cli::parse_action(&words).map_err(|_| invalid())?);
+                }
+                "value" => message.value = Some(value.parse().map_err(|_| invalid())?),
+                "muted" => message.muted = Some(value.parse().map_err(|_| invalid())?),
+                "status" => {
+                    let status = PlaybackStatus::parse(&value).ok_or_else(invalid)?;
+                    message.track.get_or_insert_with(Track::default).status = status;
+                }
+                "title" => message.track.get_or_insert_with(Track::default).title = Some(value),
+                "artist" => message.track.get_or_insert_with(Track::default).artist = Some(value),
+                "device" => message.device = Some(value),
+                "icon" => message.icon = Some(value),
+                "error" => message.error = Some(value),
+                _ => {
+                    if let Some(option) = key.strip_prefix(STYLE_PREFIX) {
+                        message.style.push((option.to_string(), value));
+                    }
+                }
+            }
+        }
+        message.kind = kind.ok_or("missing kind")?;
+        Ok(message)
+    }
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let body = self.encode();
+        if body.len() > MAX_LENGTH as usize {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("message too long ({} bytes)", body.len()),
+            ));
+        }
+        let mut packet = (body.len() as u32).to_be_bytes().to_vec();
+        packet.extend(body.as_bytes());
+        writer.write_all(&packet)
+    }
+    pub fn read_from(reader: &mut impl Read) -> Result<Self, String> {
+        let mut length = [0; 4];
+        reader
+            .read_exact(&mut length)
+            .map_err(|err| format!("failed to read message: {err}"))?;
+        let length = u32::from_be_bytes(length);
+        if length > MAX_LENGTH {
+            return Err(format!("message too long ({length} bytes)"));
+        }
+        let mut body = vec![0; length as usize];
+        reader
+            .read_exact(&mut body)
+            .map_err(|err| format!("failed to read message: {err}"))?;
+        let text = String::from_utf8(body).map_err(|_| "message is not valid UTF-8")?;
+        Self::decode(&text)
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                unescaped.push('\n');
+                chars.next();
+            }
+            ('\\', Some('r')) => {
+                unescaped.push('\r');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                unescaped.push('\\');
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(body: &[u8]) -> Vec<u8> {
+        let mut packet = (body.len() as u32).to_be_bytes().to_vec();
+        packet.extend(body);
+        packet
+    }
+
+    fn read(body: &[u8]) -> Result<Message, String> {
+        Message::read_from(&mut packet(body).as_slice())
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut message = Message::new(MessageKind::Update);
+        message.action = Some(Action::VolumeUp(5.5));
+        message.value = Some(55);
+        message.muted = Some(false);
+        message.track = Some(Track {
+            title: Some("Line\nbreak\r\nand \\n=".to_string()),
+            artist: None,
+            status: PlaybackStatus::Paused,
+        });
+        message.device = Some("Speakers\r".to_string());
+        message.icon = Some("/usr/share/icons/a=b.svg".to_string());
+        message.error = Some("failed\\".to_string());
+        message.style = vec![
+            ("color".to_string(), "#000000AA".to_string()),
+            ("font-description".to_string(), "Sans 12".to_string()),
+        ];
+        let mut buffer = Vec::new();
+        message.write_to(&mut buffer).unwrap();
+        assert_eq!(Message::read_from(&mut buffer.as_slice()), Ok(message));
+
+        let reply = Message::new(MessageKind::Reply);
+        assert_eq!(Message::decode(&reply.encode()), Ok(reply));
+    }
+
+    #[test]
+    fn skips_unknown_fields() {
+        let message = read(b"version=1\nkind=command\nfuture=x\naction=b up 5\nstyle.new=1\n");
+        let mut expected = Message::new(MessageKind::Command);
+        expected.action = Some(Action::BrightnessUp(5.0));
+        expected.style = vec![("new".to_string(), "1".to_string())];
+        assert_eq!(message, Ok(expected));
+    }
+
+    #[test]
+    fn refuses_other_versions() {
+        assert_eq!(
+            read(format!("version={}\nkind=update\n", VERSION + 1).as_bytes()),
+            Err(format!(
+                "unsupported version {}, expected {VERSION}",
+                VERSION + 1
+            ))
+        );
+        assert_eq!(
+            read(b"kind=update\nversion=1\n"),
+            Err("missing version".to_string())
+        );
+        assert_eq!(
+            read(b"version=one\nkind=update\n"),
+            Err("invalid version \"one\"".to_string())
+        );
+    }
+
+    #[test]
+    fn refuses_malformed_fields() {
+        assert_eq!(read(b"version=1\n"), Err("missing kind".to_string()));
+        assert_eq!(
+            read(b"version=1\nkind=update\nvalue\n"),
+            Err("invalid field \"value\"".to_string())
+        );
+        assert_eq!(
+            read(b"version=1\nkind=update\nvalue=300\n"),
+            Err("invalid value \"300\"".to_string())
+        );
+        assert_eq!(
+            read(b"version=1\nkind=query\n"),
+            Err("invalid kind \"query\"".to_string())
+        );
+        assert_eq!(
+            read(b"version=1\nkind=command\naction=v sideways\n"),
+            Err("invalid action \"v sideways\"".to_string())
+        );
+    }
+
+    #[test]
+    fn refuses_bad_packets() {
+        let too_long = (MAX_LENGTH + 1).to_be_bytes();
+        assert_eq!(
+            Message::read_from(&mut too_long.as_slice()),
+            Err(format!("message too long ({} bytes)", MAX_LENGTH + 1))
+        );
+        let mut truncated = packet(b"version=1\nkind=update\n");
+        truncated.truncate(10);
+        assert!(Message::read_from(&mut truncated.as_slice())
+            .unwrap_err()
+            .starts_with("failed to read message"));
+        assert!(Message::read_from(&mut [0, 0].as_slice())
+            .unwrap_err()
+            .starts_with("failed to read message"));
+        assert_eq!(
+            read(b"version=1\nkind=update\ntitle=\xff\n"),
+            Err("message is not valid UTF-8".to_string())
+        );
+    }
+
+    #[test]
+    fn refuses_to_write_long_messages() {
+        let mut message = Message::new(MessageKind::Update);
+        message.error = Some("x".repeat(MAX_LENGTH as usize));
+        let mut buffer = Vec::new();
+        let err = message.write_to(&mut buffer).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(buffer.is_empty());
+    }
+}
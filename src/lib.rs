@@ -1,26 +1,68 @@
+mod backend;
+mod backlight;
 mod cli;
+mod config;
+mod ddc;
+mod ipc;
+mod mpris;
+#[cfg(feature = "pulse")]
+mod pulse;
+mod runtime;
+mod timer;
 
+#[cfg(any(feature = "regular", feature = "wayland"))]
+mod animation;
 #[cfg(feature = "regular")]
 mod window;
 #[cfg(feature = "wayland")]
 mod wl_window;
 
+pub use backend::{BackendError, BackendResult, MediaBackend, PlaybackStatus, Track};
+pub use backlight::{BacklightBackend, KeyboardBacklightBackend};
 use cli::{Cli, NAME};
+pub use ddc::DdcBackend;
 use fs2::FileExt;
-use std::io::{Read, Write};
+use ipc::{Message, MessageKind};
+pub use mpris::{Busctl, MprisBackend, SessionBus};
+#[cfg(feature = "pulse")]
+pub use pulse::PulseBackend;
+use runtime::RuntimeDir;
+use timer::{HideTimer, Phase, SharedHideTimer};
+
+use std::os::unix::net::{UnixListener, UnixStream};
 
 #[cfg(all(feature = "regular", feature = "wayland"))]
 compile_error!("Features \"regular\" and \"wayland\" cannot be enabled at the same time");
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub enum Action {
     #[default]
     VolumeToggleMute,
     MicrophoneToggleMute,
-    VolumeUp(u8),
-    VolumeDown(u8),
-    BrightnessUp(u8),
-    BrightnessDown(u8),
+    VolumeUp(f32),
+    VolumeDown(f32),
+    BrightnessUp(f32),
+    BrightnessDown(f32),
+    /// Sets the volume to a percentage, on the perceived scale.
+    VolumeSet(f32),
+    /// Sets the brightness to a percentage, on the perceived scale.
+    BrightnessSet(f32),
+    VolumeSetMute(bool),
+    MicrophoneSetMute(bool),
+    MicrophoneUp(f32),
+    MicrophoneDown(f32),
+    /// Sets the microphone volume to a percentage, on the perceived scale.
+    MicrophoneSet(f32),
+    PlayPause,
+    NextTrack,
+    PreviousTrack,
+    /// Makes the next output device the default one.
+    VolumeNextDevice,
+    KeyboardUp(f32),
+    KeyboardDown(f32),
+    /// Sets the keyboard backlight to a percentage.
+    KeyboardSet(f32),
+    KeyboardToggle,
 }
 impl Action {
     fn is_volume_kind(&self) -> bool {
@@ -31,11 +73,109 @@ impl Action {
             Self::VolumeDown(_) => true,
             Self::BrightnessUp(_) => false,
             Self::BrightnessDown(_) => false,
+            Self::VolumeSet(_) => true,
+            Self::BrightnessSet(_) => false,
+            Self::VolumeSetMute(_) => true,
+            Self::MicrophoneSetMute(_) => true,
+            Self::MicrophoneUp(_) => true,
+            Self::MicrophoneDown(_) => true,
+            Self::MicrophoneSet(_) => true,
+            Self::PlayPause => false,
+            Self::NextTrack => false,
+            Self::PreviousTrack => false,
+            Self::VolumeNextDevice => true,
+            Self::KeyboardUp(_) => false,
+            Self::KeyboardDown(_) => false,
+            Self::KeyboardSet(_) => false,
+            Self::KeyboardToggle => false,
+        }
+    }
+    fn is_microphone_kind(&self) -> bool {
+        matches!(
+            self,
+            Self::MicrophoneToggleMute
+                | Self::MicrophoneSetMute(_)
+                | Self::MicrophoneUp(_)
+                | Self::MicrophoneDown(_)
+                | Self::MicrophoneSet(_)
+        )
+    }
+    fn is_media_kind(&self) -> bool {
+        matches!(
+            self,
+            Self::PlayPause | Self::NextTrack | Self::PreviousTrack
+        )
+    }
+    fn is_keyboard_kind(&self) -> bool {
+        matches!(
+            self,
+            Self::KeyboardUp(_)
+                | Self::KeyboardDown(_)
+                | Self::KeyboardSet(_)
+                | Self::KeyboardToggle
+        )
+    }
+    /// Name of the config file section with the options for this action.
+    fn config_section(&self) -> &'static str {
+        match self {
+            _ if self.is_microphone_kind() => "microphone",
+            _ if self.is_volume_kind() => "volume",
+            _ if self.is_media_kind() => "media",
+            _ if self.is_keyboard_kind() => "keyboard",
+            _ => "brightness",
+        }
+    }
+    /// Freedesktop icon name, without the suffix describing the level.
+    fn icon_name(&self) -> &'static str {
+        match self {
+            _ if self.is_microphone_kind() => "microphone-sensitivity",
+            _ if self.is_volume_kind() => "audio-volume",
+            _ if self.is_media_kind() => "media-playback",
+            _ if self.is_keyboard_kind() => "keyboard-brightness",
+            _ => "display-brightness",
+        }
+    }
+    fn label_prefix(&self) -> &'static str {
+        match self {
+            _ if self.is_microphone_kind() => "MIC:",
+            _ if self.is_volume_kind() => "VOL:",
+            _ if self.is_media_kind() => "MEDIA:",
+            _ if self.is_keyboard_kind() => "KBD:",
+            _ => "BRT:",
+        }
+    }
+}
+
+/// Formats the action the way it's given on the command line, e.g. `v up 5`.
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::VolumeToggleMute => write!(f, "v mute"),
+            Self::MicrophoneToggleMute => write!(f, "m mute"),
+            Self::VolumeUp(v) => write!(f, "v up {v}"),
+            Self::VolumeDown(v) => write!(f, "v down {v}"),
+            Self::BrightnessUp(v) => write!(f, "b up {v}"),
+            Self::BrightnessDown(v) => write!(f, "b down {v}"),
+            Self::VolumeSet(v) => write!(f, "v set {v}"),
+            Self::BrightnessSet(v) => write!(f, "b set {v}"),
+            Self::VolumeSetMute(mute) => write!(f, "v mute {}", on_off(*mute)),
+            Self::MicrophoneSetMute(mute) => write!(f, "m mute {}", on_off(*mute)),
+            Self::MicrophoneUp(v) => write!(f, "m up {v}"),
+            Self::MicrophoneDown(v) => write!(f, "m down {v}"),
+            Self::MicrophoneSet(v) => write!(f, "m set {v}"),
+            Self::PlayPause => write!(f, "p play-pause"),
+            Self::NextTrack => write!(f, "p next"),
+            Self::PreviousTrack => write!(f, "p previous"),
+            Self::VolumeNextDevice => write!(f, "v next-device"),
+            Self::KeyboardUp(v) => write!(f, "k up {v}"),
+            Self::KeyboardDown(v) => write!(f, "k down {v}"),
+            Self::KeyboardSet(v) => write!(f, "k set {v}"),
+            Self::KeyboardToggle => write!(f, "k toggle"),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color {
     pub r: f32,
     pub g: f32,
@@ -88,6 +228,315 @@ impl Color {
     }
 }
 
+fn on_off(value: bool) -> &'static str {
+    match value {
+        true => "on",
+        false => "off",
+    }
+}
+
+/// Icon shown next to the label.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum Icon {
+    /// Looked up in the freedesktop icon theme, depending on the level.
+    #[default]
+    Auto,
+    Hidden,
+    /// Icon name, or path of an image if it contains a `/`.
+    Custom(String),
+}
+impl std::fmt::Display for Icon {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Hidden => write!(f, "none"),
+            Self::Custom(icon) => write!(f, "{icon}"),
+        }
+    }
+}
+impl Icon {
+    /// Parses `auto`, `none`, an icon name or an image path.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "" => None,
+            "auto" => Some(Self::Auto),
+            "none" => Some(Self::Hidden),
+            _ => Some(Self::Custom(s.to_string())),
+        }
+    }
+}
+
+/// Maps the raw backend value (0-100) to the value perceived by the user
+/// (0-100). Steps are applied to the perceived value, so that they feel
+/// uniform across the whole range.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Curve {
+    #[default]
+    Linear,
+    /// Fine steps at low values, coarse ones at high values.
+    Exponential,
+    /// `raw = perceived ^ gamma`, with both normalized to 0-1.
+    Gamma(f32),
+}
+impl std::fmt::Display for Curve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Linear => write!(f, "linear"),
+            Self::Exponential => write!(f, "exponential"),
+            Self::Gamma(gamma) => write!(f, "{gamma}"),
+        }
+    }
+}
+impl Curve {
+    const EXPONENTIAL_BASE: f32 = 100.0;
+
+    /// Parses `linear`, `exponential` or a gamma value such as `2.2`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "linear" => Some(Self::Linear),
+            "exponential" => Some(Self::Exponential),
+            _ => match s.parse::<f32>() {
+                Ok(gamma) if gamma.is_finite() && gamma > 0.0 => Some(Self::Gamma(gamma)),
+                _ => None,
+            },
+        }
+    }
+    /// Boosted volumes past 100% stay linear.
+    pub fn to_perceived(&self, raw: f32) -> f32 {
+        if raw > 100.0 {
+            return raw;
+        }
+        let raw = raw.max(0.0) / 100.0;
+        let perceived = match self {
+            Self::Linear => raw,
+            Self::Exponential => {
+                (1.0 + raw * (Self::EXPONENTIAL_BASE - 1.0)).ln() / Self::EXPONENTIAL_BASE.ln()
+            }
+            Self::Gamma(gamma) => raw.powf(1.0 / gamma),
+        };
+        perceived * 100.0
+    }
+    pub fn to_raw(&self, perceived: f32) -> f32 {
+        if perceived > 100.0 {
+            return perceived;
+        }
+        let perceived = perceived.max(0.0) / 100.0;
+        let raw = match self {
+            Self::Linear => perceived,
+            Self::Exponential => {
+                (Self::EXPONENTIAL_BASE.powf(perceived) - 1.0) / (Self::EXPONENTIAL_BASE - 1.0)
+            }
+            Self::Gamma(gamma) => perceived.powf(*gamma),
+        };
+        raw * 100.0
+    }
+    /// Returns the raw increment that moves `current` by `inc` perceived
+    /// units. Moves by at least 1, or `inc` when it is smaller, so that steps
+    /// are never lost to rounding.
+    fn raw_inc(&self, current: u8, inc: f32) -> f32 {
+        if inc == 0.0 {
+            return 0.0;
+        }
+        let perceived = self.to_perceived(current as f32) + inc;
+        let raw_inc = self.to_raw(perceived) - current as f32;
+        if inc > 0.0 {
+            raw_inc.max(inc.min(1.0))
+        } else {
+            raw_inc.min(inc.max(-1.0))
+        }
+    }
+}
+
+/// Easing of the progress bar's transitions between levels.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    #[default]
+    EaseOut,
+    EaseInOut,
+}
+impl std::fmt::Display for Easing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Linear => write!(f, "linear"),
+            Self::EaseIn => write!(f, "ease-in"),
+            Self::EaseOut => write!(f, "ease-out"),
+            Self::EaseInOut => write!(f, "ease-in-out"),
+        }
+    }
+}
+impl Easing {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "linear" => Some(Self::Linear),
+            "ease-in" => Some(Self::EaseIn),
+            "ease-out" => Some(Self::EaseOut),
+            "ease-in-out" => Some(Self::EaseInOut),
+            _ => None,
+        }
+    }
+    /// Maps the elapsed fraction of the transition (0-1) to the fraction of
+    /// the way covered (0-1), with cubic curves.
+    pub fn apply(&self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => t,
+            Self::EaseIn => t.powi(3),
+            Self::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Self::EaseInOut if t < 0.5 => 4.0 * t.powi(3),
+            Self::EaseInOut => 1.0 - (2.0 - 2.0 * t).powi(3) / 2.0,
+        }
+    }
+}
+
+/// How the window appears or disappears.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum WindowAnimation {
+    None,
+    /// Through its opacity, which needs a compositor.
+    #[default]
+    Fade,
+    /// From or to the edge of the screen it is placed against.
+    Slide,
+}
+impl std::fmt::Display for WindowAnimation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Fade => write!(f, "fade"),
+            Self::Slide => write!(f, "slide"),
+        }
+    }
+}
+impl WindowAnimation {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "none" => Some(Self::None),
+            "fade" => Some(Self::Fade),
+            "slide" => Some(Self::Slide),
+            _ => None,
+        }
+    }
+    /// The duration of the animation, which doesn't take any time if there
+    /// is none.
+    fn duration(&self, seconds: f32) -> std::time::Duration {
+        match self {
+            Self::None => std::time::Duration::ZERO,
+            _ => std::time::Duration::from_secs_f32(seconds.max(0.0)),
+        }
+    }
+}
+
+/// A side of the screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+/// Where the window is placed on the screen: against a side, in a corner or
+/// centered.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Anchor {
+    Top,
+    #[default]
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+impl std::fmt::Display for Anchor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Top => write!(f, "top"),
+            Self::Bottom => write!(f, "bottom"),
+            Self::Left => write!(f, "left"),
+            Self::Right => write!(f, "right"),
+            Self::TopLeft => write!(f, "top-left"),
+            Self::TopRight => write!(f, "top-right"),
+            Self::BottomLeft => write!(f, "bottom-left"),
+            Self::BottomRight => write!(f, "bottom-right"),
+            Self::Center => write!(f, "center"),
+        }
+    }
+}
+impl Anchor {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "top" => Some(Self::Top),
+            "bottom" => Some(Self::Bottom),
+            "left" => Some(Self::Left),
+            "right" => Some(Self::Right),
+            "top-left" => Some(Self::TopLeft),
+            "top-right" => Some(Self::TopRight),
+            "bottom-left" => Some(Self::BottomLeft),
+            "bottom-right" => Some(Self::BottomRight),
+            "center" => Some(Self::Center),
+            _ => None,
+        }
+    }
+    /// Whether the window is placed against `side`. It is centered along the
+    /// sides it isn't placed against.
+    pub fn touches(&self, side: Side) -> bool {
+        match side {
+            Side::Top => matches!(self, Self::Top | Self::TopLeft | Self::TopRight),
+            Side::Bottom => matches!(self, Self::Bottom | Self::BottomLeft | Self::BottomRight),
+            Side::Left => matches!(self, Self::Left | Self::TopLeft | Self::BottomLeft),
+            Side::Right => matches!(self, Self::Right | Self::TopRight | Self::BottomRight),
+        }
+    }
+    /// The side the window slides in from and out of, the top or bottom for
+    /// corners. `None` when centered.
+    pub fn slide_side(&self) -> Option<Side> {
+        [Side::Top, Side::Bottom, Side::Left, Side::Right]
+            .into_iter()
+            .find(|side| self.touches(*side))
+    }
+}
+
+/// Monitor to show the window on.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum Monitor {
+    /// The one with the pointer.
+    Focused,
+    #[default]
+    Primary,
+    /// By position among the monitors, from 0.
+    Index(usize),
+    /// By connector name, e.g. `DP-1`.
+    Name(String),
+}
+impl std::fmt::Display for Monitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Focused => write!(f, "focused"),
+            Self::Primary => write!(f, "primary"),
+            Self::Index(index) => write!(f, "{index}"),
+            Self::Name(name) => write!(f, "{name}"),
+        }
+    }
+}
+impl Monitor {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "" => None,
+            "focused" => Some(Self::Focused),
+            "primary" => Some(Self::Primary),
+            _ => Some(match s.parse::<usize>() {
+                Ok(index) => Self::Index(index),
+                Err(_) => Self::Name(s.to_string()),
+            }),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MediaController {
     pub action: Action,
@@ -95,11 +544,69 @@ pub struct MediaController {
     pub font_description: String,
     pub width: u32,
     pub height: u32,
+    pub monitor: Monitor,
+    pub anchor: Anchor,
+    /// Space between the window and the sides of the screen it is placed
+    /// against, in px.
+    pub top: u32,
     pub bottom: u32,
+    pub left: u32,
+    pub right: u32,
     pub duration: f32,
+    /// Duration in seconds of the animation showing the window, 0 to show it
+    /// at once.
+    pub show_duration: f32,
+    pub show_animation: WindowAnimation,
+    /// Duration in seconds of the animation hiding the window once
+    /// `duration` runs out, 0 to hide it at once.
+    pub hide_duration: f32,
+    pub hide_animation: WindowAnimation,
     pub filled: char,
     pub half_filled: char,
     pub empty: char,
+    /// Filled character used past 100% when the volume is boosted.
+    pub over_filled: char,
+    /// Whether to draw the progress bar instead of using characters.
+    pub graphic_bar: bool,
+    pub bar_fill_color: Color,
+    pub bar_track_color: Color,
+    pub bar_border_color: Color,
+    /// Fill color of the drawn progress bar past 100%.
+    pub bar_over_color: Color,
+    pub icon: Icon,
+    /// Icon used instead of `icon` while muted.
+    pub muted_icon: Icon,
+    /// Backlight device to control. `None` picks one automatically.
+    pub backlight: Option<String>,
+    /// Keyboard backlight device in `/sys/class/leds` to control. `None`
+    /// picks one automatically.
+    pub keyboard_backlight: Option<String>,
+    /// Outputs whose brightness is controlled over DDC/CI instead of the
+    /// backlight, by connector name (e.g. `DP-1`) or i2c-dev path.
+    pub ddc: Vec<String>,
+    /// MPRIS player to control, e.g. `spotify`. `None` prefers the one that
+    /// is playing.
+    pub player: Option<String>,
+    /// Sink to control, or source for microphone actions, by name or alias.
+    /// `None` uses the default one.
+    pub device: Option<String>,
+    /// Highest volume (in %) that actions can reach, above 100 to allow
+    /// boosting.
+    pub max_volume: u8,
+    pub volume_curve: Curve,
+    pub brightness_curve: Curve,
+    /// Whether the progress bar shows the perceived value instead of the raw
+    /// one.
+    pub perceived_progress: bool,
+    /// Duration in seconds of the progress bar's transitions between levels,
+    /// 0 to disable them.
+    pub transition_duration: f32,
+    pub transition_easing: Easing,
+    /// Whether to stay resident and perform the actions sent by other
+    /// instances, instead of exiting once the window closes.
+    pub daemon: bool,
+    /// Separates the instances of the same user, e.g. per seat or display.
+    pub namespace: Option<String>,
 }
 impl std::default::Default for MediaController {
     fn default() -> Self {
@@ -109,161 +616,829 @@ impl std::default::Default for MediaController {
             font_description: "Monospace 13".to_string(),
             width: 300,
             height: 20,
+            monitor: Monitor::default(),
+            anchor: Anchor::default(),
+            top: 100,
             bottom: 100,
+            left: 100,
+            right: 100,
             duration: 2.0,
+            show_duration: 0.0,
+            show_animation: WindowAnimation::default(),
+            hide_duration: 0.0,
+            hide_animation: WindowAnimation::default(),
             filled: '█',
             half_filled: '▌',
             empty: ' ',
+            over_filled: '▓',
+            graphic_bar: false,
+            bar_fill_color: Color::new(1.0, 1.0, 1.0, 1.0),
+            bar_track_color: Color::new(1.0, 1.0, 1.0, 0.2),
+            bar_border_color: Color::new(1.0, 1.0, 1.0, 1.0),
+            bar_over_color: Color::new(1.0, 0.4, 0.4, 1.0),
+            icon: Icon::default(),
+            muted_icon: Icon::default(),
+            backlight: None,
+            keyboard_backlight: None,
+            ddc: Vec::new(),
+            player: None,
+            device: None,
+            max_volume: 100,
+            volume_curve: Curve::default(),
+            brightness_curve: Curve::default(),
+            perceived_progress: false,
+            transition_duration: 0.15,
+            transition_easing: Easing::default(),
+            daemon: false,
+            namespace: None,
         }
     }
 }
 
-pub struct MediaControllerApp {
-    /// Should return whether the volume is muted.
-    pub get_volume_mute: fn() -> bool,
+impl MediaController {
+    /// Returns a copy with the action and style overrides of `message`.
+    fn for_message(&self, message: &Message) -> Self {
+        let mut controller = self.clone();
+        if let Some(action) = message.action {
+            controller.action = action;
+        }
+        for (option, value) in &message.style {
+            if controller.set_option(option, value).is_none() {
+                eprintln!("{NAME}: ignoring invalid override {option}={value:?}");
+            }
+        }
+        controller
+    }
+    /// Returns the sink that volume actions should control, `None` for the
+    /// default one.
+    pub fn sink(&self) -> Option<&str> {
+        let action = self.action;
+        // Cycling always starts from the default sink.
+        let applies = action.is_volume_kind()
+            && !action.is_microphone_kind()
+            && action != Action::VolumeNextDevice;
+        self.device.as_deref().filter(|_| applies)
+    }
+    /// Returns the source that microphone actions should control, `None` for
+    /// the default one.
+    pub fn source(&self) -> Option<&str> {
+        self.device
+            .as_deref()
+            .filter(|_| self.action.is_microphone_kind())
+    }
+    /// Returns the space between the window and `side` of the screen, 0
+    /// unless the window is placed against it.
+    pub fn margin(&self, side: Side) -> u32 {
+        if !self.anchor.touches(side) {
+            return 0;
+        }
+        match side {
+            Side::Top => self.top,
+            Side::Bottom => self.bottom,
+            Side::Left => self.left,
+            Side::Right => self.right,
+        }
+    }
+}
 
-    /// Should return whether the microphone is muted.
-    pub get_microphone_mute: fn() -> bool,
+/// The state of whatever an action changed, as reported by the backend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reading {
+    pub action: Action,
+    /// Level (0-100). `None` if the action doesn't have one, or if it isn't
+    /// displayed because of the mute state.
+    pub value: Option<u8>,
+    pub muted: bool,
+    /// What the media player is playing, for playback actions.
+    pub track: Option<Track>,
+    /// Description of the audio device, when it was picked with `--device`
+    /// or changed by the action.
+    pub device: Option<String>,
+}
+impl Reading {
+    /// Renders the text shown in the window.
+    pub fn label(&self, controller: &MediaController) -> String {
+        let caption = self.caption();
+        match self.level(controller) {
+            // Otherwise the window draws it next to the caption.
+            Some(level) if !controller.graphic_bar => format!(
+                "{caption} {}",
+                BarChars::new(controller).render(level, self.maximum(controller))
+            ),
+            _ => caption,
+        }
+    }
+    /// Returns the text that goes before the progress bar, or all of it if
+    /// there's no bar.
+    fn caption(&self) -> String {
+        let label = self.state_label();
+        match &self.device {
+            Some(device) => format!("{device} · {label}"),
+            None => label,
+        }
+    }
+    fn state_label(&self) -> String {
+        if let Some(track) = &self.track {
+            let status = match track.status {
+                PlaybackStatus::Playing => "PLAYING",
+                PlaybackStatus::Paused => "PAUSED",
+                PlaybackStatus::Stopped => "STOPPED",
+            };
+            return match (&track.artist, &track.title) {
+                (Some(artist), Some(title)) => format!("{status}: {artist} - {title}"),
+                (None, Some(title)) => format!("{status}: {title}"),
+                _ => status.to_string(),
+            };
+        }
+        if self.action.is_microphone_kind() {
+            if self.muted {
+                return "MIC OFF".to_string();
+            } else if self.value.is_none() {
+                return "MIC ON".to_string();
+            }
+        }
+        if self.muted {
+            return "MUTED".to_string();
+        }
+        self.action.label_prefix().to_string()
+    }
+    /// Returns the value shown by the progress bar, if there's one.
+    pub fn level(&self, controller: &MediaController) -> Option<u8> {
+        if self.action.is_media_kind() || self.muted {
+            return None;
+        }
+        // The microphone volume is only known when the backend supports it.
+        let value = match self.value {
+            Some(value) => value,
+            None if self.action.is_microphone_kind() => return None,
+            None => 0,
+        };
+        // Keyboard backlights have too few levels for a curve to matter.
+        let curve = match self.action {
+            action if action.is_keyboard_kind() => Curve::Linear,
+            action if action.is_volume_kind() => controller.volume_curve,
+            _ => controller.brightness_curve,
+        };
+        Some(Self::displayed(value, curve, controller))
+    }
+    /// Returns the value at the end of the progress bar, which is above 100
+    /// for boosted volumes.
+    pub fn maximum(&self, controller: &MediaController) -> u8 {
+        if self.action.is_volume_kind() && !self.action.is_microphone_kind() {
+            controller.max_volume.max(100)
+        } else {
+            100
+        }
+    }
+    /// Returns the freedesktop icon name, e.g. `audio-volume-medium`.
+    pub fn icon_name(&self, controller: &MediaController) -> String {
+        if let Some(track) = &self.track {
+            return match track.status {
+                PlaybackStatus::Playing => "media-playback-start",
+                PlaybackStatus::Paused => "media-playback-pause",
+                PlaybackStatus::Stopped => "media-playback-stop",
+            }
+            .to_string();
+        }
+        let level = match self.level(controller) {
+            _ if self.muted => "muted",
+            None => "high",
+            // Themes use the muted icon for a silent sink too.
+            Some(0) if self.action.is_volume_kind() => "muted",
+            Some(0..=33) => "low",
+            Some(34..=66) => "medium",
+            Some(101..) if self.action.is_volume_kind() => "overamplified",
+            Some(_) => "high",
+        };
+        format!("{}-{level}", self.action.icon_name())
+    }
+    fn displayed(raw: u8, curve: Curve, controller: &MediaController) -> u8 {
+        if !controller.perceived_progress {
+            return raw;
+        }
+        curve.to_perceived(raw as f32).round() as u8
+    }
+    fn from_message(message: &Message) -> Option<Self> {
+        if message.error.is_some() {
+            return None;
+        }
+        Some(Self {
+            action: message.action?,
+            value: message.value,
+            muted: message.muted.unwrap_or(false),
+            track: message.track.clone(),
+            device: message.device.clone(),
+        })
+    }
+}
 
-    /// Should return the volume (0-100).
-    pub get_volume: fn() -> u8,
-    /// Should return the brightness (0-100).
-    pub get_brightness: fn() -> u8,
+/// Colors of the drawn progress bar.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct BarColors {
+    fill: Color,
+    track: Color,
+    border: Color,
+    /// Fill past 100%.
+    over: Color,
+}
 
-    /// Should increment the volume. To decrement use a negative value.
-    pub inc_volume: fn(i8),
-    /// Should increment the brightness. To decrement use a negative value.
-    pub inc_brightness: fn(i8),
+/// Characters of the progress bar when it isn't drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BarChars {
+    filled: char,
+    over_filled: char,
+    half_filled: char,
+    empty: char,
+}
+impl BarChars {
+    fn new(controller: &MediaController) -> Self {
+        Self {
+            filled: controller.filled,
+            over_filled: controller.over_filled,
+            half_filled: controller.half_filled,
+            empty: controller.empty,
+        }
+    }
+    fn render(&self, level: u8, maximum: u8) -> String {
+        MediaControllerApp::_progress(
+            level,
+            maximum,
+            self.filled,
+            self.over_filled,
+            self.half_filled,
+            self.empty,
+        )
+    }
+}
 
-    /// Should toggle volume mute.
-    pub toggle_volume_mute: fn(),
+/// What the window displays.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct OsdState {
+    /// Text before the progress bar, or all of it if there's no bar.
+    label: String,
+    /// Icon name or image path. `None` hides it.
+    icon: Option<String>,
+    /// Value of the progress bar (0-`bar_maximum`). `None` hides it.
+    level: Option<u8>,
+    /// Value at the end of the progress bar, 100 unless boosting.
+    bar_maximum: u8,
+    /// `None` if the progress bar is drawn, otherwise it is rendered with
+    /// these after the label.
+    bar_chars: Option<BarChars>,
+    /// How the progress bar moves to a new level.
+    transition: std::time::Duration,
+    easing: Easing,
+    /// Only false while a daemon is idle.
+    visible: bool,
+    /// How the window appears, when it is shown.
+    show_animation: WindowAnimation,
+    show_duration: std::time::Duration,
+    /// Whether the window is disappearing, over `hide_duration`.
+    hiding: bool,
+    hide_animation: WindowAnimation,
+    hide_duration: std::time::Duration,
+    color: Color,
+    bar_colors: BarColors,
+    font_description: String,
+}
+impl OsdState {
+    /// `reading` is `None` if the backend failed.
+    fn new(controller: &MediaController, reading: Option<&Reading>) -> Self {
+        let label = match reading {
+            Some(reading) => reading.caption(),
+            None => format!("{} unavailable", controller.action.label_prefix()),
+        };
+        let level = reading.and_then(|reading| reading.level(controller));
+        let icon = match reading {
+            Some(reading) if reading.muted => &controller.muted_icon,
+            _ => &controller.icon,
+        };
+        let icon = match icon {
+            Icon::Auto => Some(match reading {
+                Some(reading) => reading.icon_name(controller),
+                None => controller.action.icon_name().to_string(),
+            }),
+            Icon::Hidden => None,
+            Icon::Custom(icon) => Some(icon.clone()),
+        };
+        let bar_maximum = reading.map_or(100, |reading| reading.maximum(controller));
+        Self {
+            label,
+            icon,
+            level,
+            bar_maximum,
+            bar_chars: (!controller.graphic_bar).then(|| BarChars::new(controller)),
+            transition: std::time::Duration::from_secs_f32(controller.transition_duration.max(0.0)),
+            easing: controller.transition_easing,
+            visible: true,
+            show_animation: controller.show_animation,
+            show_duration: controller.show_animation.duration(controller.show_duration),
+            hiding: false,
+            hide_animation: controller.hide_animation,
+            hide_duration: controller.hide_animation.duration(controller.hide_duration),
+            color: controller.color,
+            bar_colors: BarColors {
+                fill: controller.bar_fill_color,
+                track: controller.bar_track_color,
+                border: controller.bar_border_color,
+                over: controller.bar_over_color,
+            },
+            font_description: controller.font_description.clone(),
+        }
+    }
+    /// Renders the label followed by the progress bar at `level`, unless it
+    /// is drawn.
+    fn text(&self, level: Option<u8>) -> String {
+        match (level, self.bar_chars) {
+            (Some(level), Some(chars)) => {
+                format!("{} {}", self.label, chars.render(level, self.bar_maximum))
+            }
+            _ => self.label.clone(),
+        }
+    }
+}
+/// The label, followed by the progress bar or the value of the drawn one.
+impl std::fmt::Display for OsdState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.level, self.bar_chars) {
+            (Some(level), None) => write!(f, "{} {level}%", self.label),
+            _ => write!(f, "{}", self.text(self.level)),
+        }
+    }
+}
+
+type OnChange = Box<dyn Fn() + Send>;
 
-    /// Should toggle microphone mute.
-    pub toggle_microphone_mute: fn(),
+/// What the window displays, shared with the threads that change it.
+#[derive(Clone, Default)]
+struct SharedOsdState {
+    state: std::sync::Arc<std::sync::Mutex<OsdState>>,
+    /// Called after every change, from the thread that made it.
+    on_change: std::sync::Arc<std::sync::Mutex<Option<OnChange>>>,
+}
+impl SharedOsdState {
+    fn new(state: OsdState) -> Self {
+        let shared = Self::default();
+        *shared.lock() = state;
+        shared
+    }
+    fn lock(&self) -> std::sync::MutexGuard<'_, OsdState> {
+        self.state.lock().unwrap()
+    }
+    /// Changes the state and lets the window know.
+    fn update(&self, update: impl FnOnce(&mut OsdState)) {
+        update(&mut self.lock());
+        if let Some(on_change) = &*self.on_change.lock().unwrap() {
+            on_change();
+        }
+    }
+    /// Replaces the callback run after every change. The window uses it to
+    /// wake up its main loop instead of polling.
+    #[cfg(any(feature = "regular", feature = "wayland"))]
+    fn connect_changed(&self, on_change: impl Fn() + Send + 'static) {
+        *self.on_change.lock().unwrap() = Some(Box::new(on_change));
+    }
+}
+
+pub struct MediaControllerApp {
+    /// Performs the media operations and reports the current state.
+    pub backend: Box<dyn MediaBackend>,
 
     /// Pass `Some` to use custom options.
     /// Pass `None` to manage them through command line arguments.
     pub custom_controller: Option<MediaController>,
 }
 impl MediaControllerApp {
-    pub fn run(&self) {
+    /// Performs the action and displays the result.
+    ///
+    /// Invalid arguments are printed to stderr and returned. Backend errors
+    /// are printed to stderr and shown in the window instead of the progress
+    /// bar, e.g. "VOL: unavailable". In that case the process exits with a
+    /// non-zero code, or the error is returned if the window is owned by
+    /// another instance.
+    pub fn run(&mut self) -> BackendResult<()> {
         let controller = match &self.custom_controller {
             Some(controller) => controller.clone(),
             None => match MediaController::from_args() {
-                Some(controller) => controller,
-                None => {
+                Ok(Some(controller)) => controller,
+                Ok(None) => {
                     MediaController::print_usage();
-                    return;
+                    return Ok(());
+                }
+                Err(err) => {
+                    eprintln!("{NAME}: {err}");
+                    return Err(err.into());
                 }
             },
         };
 
-        match controller.action {
-            Action::VolumeUp(v) => (self.inc_volume)(v as i8),
-            Action::VolumeDown(v) => (self.inc_volume)(-(v as i8)),
-            Action::VolumeToggleMute => (self.toggle_volume_mute)(),
-            Action::MicrophoneToggleMute => (self.toggle_microphone_mute)(),
-            Action::BrightnessUp(v) => (self.inc_brightness)(v as i8),
-            Action::BrightnessDown(v) => (self.inc_brightness)(-(v as i8)),
-        };
+        let runtime_dir = RuntimeDir::new(controller.namespace.as_deref())
+            .map_err(BackendError::from)
+            .inspect_err(|err| eprintln!("{NAME}: {err}"))?;
 
-        let label_text = self.label(
-            controller.action,
-            controller.filled,
-            controller.half_filled,
-            controller.empty,
-        );
-        println!("{label_text}");
+        if controller.daemon {
+            return self.run_daemon(controller, &runtime_dir);
+        }
+
+        // Fails if there's no daemon, or if it died and left a stale socket.
+        if let Ok(stream) = UnixStream::connect(runtime_dir.daemon_socket()) {
+            println!("A daemon is running. Sending the action to it...");
+            return Self::send_to_daemon(stream, &controller).inspect_err(|err| {
+                eprintln!("{NAME}: {err}");
+            });
+        }
 
-        let lock_p = format!("/tmp/{NAME}.lock");
-        let socket_p = format!("/tmp/{NAME}.sock");
+        let result = self.update(&controller);
+        let state = OsdState::new(&controller, result.as_ref().ok());
+        println!("{state}");
+
+        let socket_p = runtime_dir.socket();
 
         let lock = std::fs::OpenOptions::new()
             .write(true)
             .create(true)
             .truncate(true)
-            .open(lock_p)
-            .unwrap();
+            .open(runtime_dir.lock())
+            .map_err(BackendError::from)
+            .inspect_err(|err| eprintln!("{NAME}: {err}"))?;
 
         if lock.try_lock_exclusive().is_err() {
             println!("Another instance is already running. Updating existing window...");
-            std::os::unix::net::UnixStream::connect(socket_p)
-                .unwrap()
-                .write_all(label_text.as_bytes())
-                .unwrap();
-            return;
+            let mut message = Message::new(MessageKind::Update);
+            message.action = Some(controller.action);
+            message.style = controller.overrides();
+            match &result {
+                Ok(reading) => {
+                    message.value = reading.value;
+                    message.muted = Some(reading.muted);
+                    message.track = reading.track.clone();
+                    message.device = reading.device.clone();
+                }
+                Err(err) => message.error = Some(err.to_string()),
+            }
+            message.icon = state.icon.clone();
+            UnixStream::connect(&socket_p)
+                .and_then(|mut stream| {
+                    ipc::set_timeouts(&stream, ipc::TIMEOUT)?;
+                    message.write_to(&mut stream)
+                })
+                .map_err(|err| {
+                    BackendError::from(format!(
+                        "failed to update the window through {}: {err}",
+                        socket_p.display()
+                    ))
+                })
+                .inspect_err(|err| eprintln!("{NAME}: {err}"))?;
+            return result.map(|_| ());
         }
 
-        let shared = std::sync::Arc::new(std::sync::Mutex::new(label_text.clone()));
+        let shared = SharedOsdState::new(state);
 
-        let kill_countdown = std::sync::Arc::new(std::sync::Mutex::new(1));
+        let timer = Self::hide_timer(&controller);
 
         let shared_2 = shared.clone();
-        let kill_countdown_2 = kill_countdown.clone();
+        let timer_2 = timer.clone();
+        let controller_2 = controller.clone();
         std::thread::spawn(move || {
+            // We hold the lock, so whatever is left there is stale.
             let _ = std::fs::remove_file(&socket_p);
-            let listener = std::os::unix::net::UnixListener::bind(socket_p).unwrap();
+            let listener = match UnixListener::bind(&socket_p) {
+                Ok(listener) => listener,
+                Err(err) => {
+                    eprintln!("{NAME}: failed to bind {}: {err}", socket_p.display());
+                    return;
+                }
+            };
             for mut stream in listener.incoming().flatten() {
-                let mut b = [0; 1024];
-                let data_size = stream.read(&mut b).unwrap();
-                let data = std::str::from_utf8(&b[..data_size]).unwrap();
-                println!("Received from another instance: {data}");
-                let mut label = shared_2.lock().unwrap();
-                let mut kill_countdown = kill_countdown_2.lock().unwrap();
-                *kill_countdown = if *kill_countdown >= 2 {
-                    2
-                } else {
-                    *kill_countdown + 1
+                if let Err(err) = ipc::set_timeouts(&stream, ipc::TIMEOUT) {
+                    eprintln!("{NAME}: {err}");
+                    continue;
+                }
+                let message = match Message::read_from(&mut stream) {
+                    Ok(message) if message.kind == MessageKind::Update => message,
+                    Ok(message) => {
+                        eprintln!("{NAME}: ignoring unexpected {:?} message", message.kind);
+                        continue;
+                    }
+                    Err(err) => {
+                        eprintln!("{NAME}: ignoring malformed message: {err}");
+                        continue;
+                    }
                 };
-                *label = data.to_string();
-                stream.shutdown(std::net::Shutdown::Both).unwrap();
-                drop(stream);
+                if message.action.is_none() {
+                    eprintln!("{NAME}: ignoring message without an action");
+                    continue;
+                }
+                let controller = controller_2.for_message(&message);
+                let mut state =
+                    OsdState::new(&controller, Reading::from_message(&message).as_ref());
+                if message.icon.is_some() {
+                    state.icon = message.icon;
+                }
+                println!("Received from another instance: {state}");
+                // Restarted first, so that the window can't be hidden right
+                // after showing the new state.
+                timer_2.restart();
+                shared_2.update(|shared| *shared = state);
             }
         });
+        Self::spawn_hide_timer(&controller, shared.clone(), timer);
+
+        Self::spawn_window(&controller, shared);
+
+        result.map(|_| ())
+    }
+    /// Stays resident with the window hidden, and shows it whenever another
+    /// instance sends an action to perform.
+    fn run_daemon(
+        &mut self,
+        controller: MediaController,
+        runtime_dir: &RuntimeDir,
+    ) -> BackendResult<()> {
+        let lock_p = runtime_dir.daemon_lock();
+        let socket_p = runtime_dir.daemon_socket();
+
+        let listener = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(lock_p)
+            .map_err(BackendError::from)
+            .and_then(|lock| {
+                lock.try_lock_exclusive()
+                    .map_err(|_| BackendError::from("a daemon is already running"))?;
+                // The lock is released when the process exits.
+                std::mem::forget(lock);
+                let _ = std::fs::remove_file(&socket_p);
+                Ok(UnixListener::bind(&socket_p)?)
+            })
+            .inspect_err(|err| eprintln!("{NAME}: {err}"))?;
+        println!("Running as a daemon, listening on {}", socket_p.display());
+
+        let shared = SharedOsdState::default();
+        let timer = Self::hide_timer(&controller);
+        // The window starts hidden.
+        timer.lock().stop();
+        Self::spawn_hide_timer(&controller, shared.clone(), timer.clone());
+
+        let window_controller = controller.clone();
+        let window_shared = shared.clone();
+        std::thread::scope(move |scope| {
+            scope.spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    self.handle_command(stream, &controller, &shared, &timer);
+                }
+            });
+            Self::spawn_window(&window_controller, window_shared);
+        });
+        Ok(())
+    }
+    /// Performs an action received by the daemon and replies with the
+    /// result.
+    fn handle_command(
+        &mut self,
+        mut stream: UnixStream,
+        controller: &MediaController,
+        shared: &SharedOsdState,
+        timer: &SharedHideTimer,
+    ) {
+        if let Err(err) = ipc::set_timeouts(&stream, ipc::TIMEOUT) {
+            eprintln!("{NAME}: {err}");
+            return;
+        }
+        let mut reply = Message::new(MessageKind::Reply);
+        match Message::read_from(&mut stream) {
+            Ok(message) if message.kind == MessageKind::Command && message.action.is_some() => {
+                let controller = controller.for_message(&message);
+                let result = self.update(&controller);
+                let state = OsdState::new(&controller, result.as_ref().ok());
+                println!("{state}");
+                timer.restart();
+                shared.update(|shared| *shared = state);
+                match result {
+                    Ok(reading) => {
+                        reply.value = reading.value;
+                        reply.muted = Some(reading.muted);
+                        reply.track = reading.track;
+                        reply.device = reading.device;
+                    }
+                    Err(err) => reply.error = Some(err.to_string()),
+                }
+            }
+            Ok(message) => {
+                reply.error = Some(format!("expected a command, got {:?}", message.kind));
+            }
+            Err(err) => {
+                eprintln!("{NAME}: ignoring malformed message: {err}");
+                reply.error = Some(err);
+            }
+        }
+        if let Err(err) = reply.write_to(&mut stream) {
+            eprintln!("{NAME}: failed to reply: {err}");
+        }
+    }
+    fn send_to_daemon(mut stream: UnixStream, controller: &MediaController) -> BackendResult<()> {
+        ipc::set_timeouts(&stream, ipc::REPLY_TIMEOUT)?;
+        let mut command = Message::new(MessageKind::Command);
+        command.action = Some(controller.action);
+        command.style = controller.overrides();
+        command.write_to(&mut stream)?;
+        let reply = Message::read_from(&mut stream)?;
+        match reply.error {
+            Some(err) => Err(err.into()),
+            None => Ok(()),
+        }
+    }
+    /// Performs the action and reads back the resulting state.
+    fn update(&mut self, controller: &MediaController) -> BackendResult<Reading> {
+        self.backend
+            .configure(controller)
+            .and_then(|_| self.perform(controller))
+            .and_then(|_| self.read(controller))
+            .inspect_err(|err| eprintln!("{NAME}: {err}"))
+    }
+    /// Starts counting down the lifespan of the window.
+    fn hide_timer(controller: &MediaController) -> SharedHideTimer {
+        let duration = std::time::Duration::from_secs_f32(controller.duration.max(0.0));
+        let fade_out = controller.hide_animation.duration(controller.hide_duration);
+        SharedHideTimer::new(HideTimer::new(duration, fade_out))
+    }
+    /// Animates the window out and hides it once `timer` runs out. Unless
+    /// running as a daemon, the window then closes, which ends `run`.
+    fn spawn_hide_timer(
+        controller: &MediaController,
+        shared: SharedOsdState,
+        timer: SharedHideTimer,
+    ) {
+        let daemon = controller.daemon;
         std::thread::spawn(move || {
-            while *kill_countdown.lock().unwrap() != 0 {
-                std::thread::sleep(std::time::Duration::from_secs_f32(controller.duration));
-                *kill_countdown.lock().unwrap() -= 1;
+            let mut guard = timer.lock();
+            loop {
+                guard = match guard.phase() {
+                    Phase::Shown(left) => timer.wait(guard, Some(left)),
+                    Phase::Fading(left) => {
+                        if !shared.lock().hiding {
+                            shared.update(|shared| shared.hiding = true);
+                        }
+                        timer.wait(guard, Some(left))
+                    }
+                    Phase::Expired => {
+                        if !daemon {
+                            println!("Closing...");
+                        }
+                        guard.stop();
+                        shared.update(|shared| shared.visible = false);
+                        guard
+                    }
+                    Phase::Stopped => timer.wait(guard, None),
+                };
             }
-            println!("Closing...");
-            std::process::exit(0);
         });
-
+    }
+    #[allow(unused_variables)]
+    fn spawn_window(controller: &MediaController, shared: SharedOsdState) {
         #[cfg(feature = "regular")]
         window::spawn_window(controller.clone(), shared);
 
         #[cfg(feature = "wayland")]
         wl_window::spawn_wl_window(controller.clone(), shared);
     }
-    pub fn label(&self, action: Action, full: char, half_full: char, empty: char) -> String {
-        if matches!(action, Action::MicrophoneToggleMute) {
-            if (self.get_microphone_mute)() {
-                return "MIC OFF".to_string();
-            } else {
-                return "MIC ON".to_string();
+    fn perform(&mut self, controller: &MediaController) -> BackendResult<()> {
+        match controller.action {
+            Action::VolumeUp(v) => self.inc_volume(v, controller.volume_curve),
+            Action::VolumeDown(v) => self.inc_volume(-v, controller.volume_curve),
+            Action::VolumeToggleMute => self.backend.toggle_volume_mute(),
+            Action::MicrophoneToggleMute => self.backend.toggle_microphone_mute(),
+            Action::BrightnessUp(v) => self.inc_brightness(v, controller.brightness_curve),
+            Action::BrightnessDown(v) => self.inc_brightness(-v, controller.brightness_curve),
+            Action::VolumeSet(v) => {
+                let v = v.min(controller.max_volume.max(100) as f32);
+                let raw = controller.volume_curve.to_raw(v);
+                self.backend.set_volume(raw)
             }
+            Action::BrightnessSet(v) => {
+                let raw = controller.brightness_curve.to_raw(v);
+                self.backend.set_brightness(raw)
+            }
+            Action::VolumeSetMute(mute) => self.backend.set_volume_mute(mute),
+            Action::MicrophoneSetMute(mute) => self.backend.set_microphone_mute(mute),
+            Action::MicrophoneUp(v) => self.inc_microphone_volume(v, controller.volume_curve),
+            Action::MicrophoneDown(v) => self.inc_microphone_volume(-v, controller.volume_curve),
+            Action::MicrophoneSet(v) => {
+                let raw = controller.volume_curve.to_raw(v);
+                self.backend.set_microphone_volume(raw)
+            }
+            Action::PlayPause => self.backend.play_pause(),
+            Action::NextTrack => self.backend.next_track(),
+            Action::PreviousTrack => self.backend.previous_track(),
+            Action::VolumeNextDevice => self.backend.next_volume_device(),
+            Action::KeyboardUp(v) => self.backend.inc_keyboard_brightness(v),
+            Action::KeyboardDown(v) => self.backend.inc_keyboard_brightness(-v),
+            Action::KeyboardSet(v) => self.backend.set_keyboard_brightness(v),
+            Action::KeyboardToggle => self.backend.toggle_keyboard_backlight(),
         }
-        let is_volume = action.is_volume_kind();
-        if !is_volume {
-            let brightness = (self.get_brightness)();
-            return format!(
-                "BRT: {}",
-                Self::_progress(brightness, full, half_full, empty)
-            );
+    }
+    fn inc_volume(&mut self, inc: f32, curve: Curve) -> BackendResult<()> {
+        let inc = match curve {
+            Curve::Linear => inc,
+            _ => curve.raw_inc(self.backend.get_volume()?, inc),
+        };
+        self.backend.inc_volume(inc)
+    }
+    fn inc_microphone_volume(&mut self, inc: f32, curve: Curve) -> BackendResult<()> {
+        let inc = match curve {
+            Curve::Linear => inc,
+            _ => curve.raw_inc(self.backend.get_microphone_volume()?, inc),
+        };
+        self.backend.inc_microphone_volume(inc)
+    }
+    fn inc_brightness(&mut self, inc: f32, curve: Curve) -> BackendResult<()> {
+        let inc = match curve {
+            Curve::Linear => inc,
+            _ => curve.raw_inc(self.backend.get_brightness()?, inc),
+        };
+        self.backend.inc_brightness(inc)
+    }
+    /// Reads the state of whatever the action changes.
+    pub fn read(&mut self, controller: &MediaController) -> BackendResult<Reading> {
+        let action = controller.action;
+        if action.is_media_kind() {
+            return Ok(Reading {
+                action,
+                value: None,
+                muted: false,
+                track: Some(self.backend.get_track()?),
+                device: None,
+            });
         }
-        if (self.get_volume_mute)() {
-            return "MUTED".to_string();
+        let (value, muted) = if action.is_microphone_kind() {
+            if self.backend.get_microphone_mute()? {
+                (None, true)
+            } else {
+                // Backends that only handle the mute state still get "MIC ON".
+                match self.backend.get_microphone_volume() {
+                    Ok(volume) => (Some(volume), false),
+                    Err(BackendError::Unsupported(_)) => (None, false),
+                    Err(err) => return Err(err),
+                }
+            }
+        } else if action.is_keyboard_kind() {
+            (Some(self.backend.get_keyboard_brightness()?), false)
+        } else if !action.is_volume_kind() {
+            (Some(self.backend.get_brightness()?), false)
+        } else if self.backend.get_volume_mute()? {
+            (None, true)
+        } else {
+            (Some(self.backend.get_volume()?), false)
+        };
+        let device = if controller.device.is_none() && action != Action::VolumeNextDevice {
+            None
+        } else if action.is_microphone_kind() {
+            Self::device_description(self.backend.get_microphone_device())?
+        } else if action.is_volume_kind() {
+            Self::device_description(self.backend.get_volume_device())?
+        } else {
+            None
+        };
+        Ok(Reading {
+            action,
+            value,
+            muted,
+            track: None,
+            device,
+        })
+    }
+    /// The description is left out if the backend can't provide it.
+    fn device_description(result: BackendResult<String>) -> BackendResult<Option<String>> {
+        match result {
+            Ok(description) => Ok(Some(description)),
+            Err(BackendError::Unsupported(_)) => Ok(None),
+            Err(err) => Err(err),
         }
-        let volume = (self.get_volume)();
-        format!("VOL: {}", Self::_progress(volume, full, half_full, empty))
     }
-    fn _progress(percentage: u8, full: char, half_full: char, empty: char) -> String {
-        assert!(percentage <= 100);
-        let progress = percentage as f32 / 10.0;
+    pub fn label(&mut self, controller: &MediaController) -> BackendResult<String> {
+        Ok(self.read(controller)?.label(controller))
+    }
+    /// Renders 10 cells spanning 0 to `maximum`, the ones past 100% with
+    /// `over_full`. Values out of range fill the whole bar.
+    fn _progress(
+        percentage: u8,
+        maximum: u8,
+        full: char,
+        over_full: char,
+        half_full: char,
+        empty: char,
+    ) -> String {
+        let maximum = maximum.max(1);
+        let clamped = percentage.min(maximum);
+        let progress = clamped as f32 * 10.0 / maximum as f32;
         let filled_count = progress as usize;
-        let middle_count = (percentage != 100) as usize;
+        let middle_count = (clamped != maximum) as usize;
         let empty_count = 10_usize.saturating_sub(progress as usize).saturating_sub(1);
-        let progress_str = std::iter::repeat_n(full, filled_count)
+        // The cell holding 100% is shown as normal if most of it is below.
+        let normal_count = (1000.0 / maximum as f32).round() as usize;
+        let progress_str = (0..filled_count)
+            .map(|i| if i < normal_count { full } else { over_full })
             .chain(std::iter::repeat_n(
                 if progress.ceil() - progress >= 0.5 {
                     half_full
@@ -277,3 +1452,252 @@ impl MediaControllerApp {
         format!("{progress_str}{percentage:>4}%")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVES: [Curve; 4] = [
+        Curve::Linear,
+        Curve::Exponential,
+        Curve::Gamma(2.2),
+        Curve::Gamma(0.5),
+    ];
+
+    #[test]
+    fn curves_round_trip() {
+        for curve in CURVES {
+            for raw in (0..=150).map(|raw| raw as f32) {
+                let back = curve.to_raw(curve.to_perceived(raw));
+                assert!((back - raw).abs() < 1e-3, "{curve}: {raw} -> {back}");
+            }
+            assert_eq!(curve.to_perceived(0.0), 0.0, "{curve}");
+            assert!((curve.to_perceived(100.0) - 100.0).abs() < 1e-3, "{curve}");
+        }
+    }
+
+    #[test]
+    fn curves_stay_linear_past_100() {
+        for curve in CURVES {
+            assert_eq!(curve.to_perceived(120.0), 120.0, "{curve}");
+            assert_eq!(curve.to_raw(120.0), 120.0, "{curve}");
+        }
+    }
+
+    #[test]
+    fn raw_inc_moves_at_least_one_step() {
+        for curve in CURVES {
+            for current in [0, 1, 50, 99, 100] {
+                let up = curve.raw_inc(current, 1.0);
+                let down = curve.raw_inc(current, -1.0);
+                assert!(up >= 1.0, "{curve} at {current}: {up}");
+                assert!(down <= -1.0, "{curve} at {current}: {down}");
+                // Smaller steps move by themselves at least.
+                assert!(curve.raw_inc(current, 0.5) >= 0.5, "{curve} at {current}");
+                assert!(curve.raw_inc(current, -0.5) <= -0.5, "{curve} at {current}");
+            }
+            assert_eq!(curve.raw_inc(50, 0.0), 0.0, "{curve}");
+        }
+        // The exponential curve is steep at the top and flat at the bottom.
+        assert_eq!(Curve::Exponential.raw_inc(0, 1.0), 1.0);
+        assert!(Curve::Exponential.raw_inc(100, -1.0) < -4.0);
+        assert!((Curve::Linear.raw_inc(40, 5.0) - 5.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn overrides_replace_the_receivers_options() {
+        // The receiver opened its window for a volume action, with a
+        // `[volume]` section that sets the color and device.
+        let receiver = MediaController {
+            action: Action::VolumeUp(5.0),
+            color: Color::from_hex("#ff0000").unwrap(),
+            device: Some("headphones".to_string()),
+            graphic_bar: true,
+            ..MediaController::default()
+        };
+        let sender = MediaController {
+            action: Action::BrightnessUp(5.0),
+            transition_easing: Easing::Linear,
+            icon: Icon::Hidden,
+            ..MediaController::default()
+        };
+        let mut message = Message::new(MessageKind::Update);
+        message.action = Some(sender.action);
+        message.style = sender.overrides();
+
+        let received = receiver.for_message(&message);
+        assert_eq!(received.action, sender.action);
+        assert_eq!(received.color, sender.color);
+        assert_eq!(received.device, None);
+        assert!(!received.graphic_bar);
+        assert_eq!(received.transition_easing, Easing::Linear);
+        assert_eq!(received.icon, Icon::Hidden);
+        assert_eq!(received.overrides(), sender.overrides());
+    }
+
+    fn progress(percentage: u8, maximum: u8) -> String {
+        MediaControllerApp::_progress(percentage, maximum, '#', '+', '-', '.')
+    }
+
+    #[test]
+    fn progress_fills_cells() {
+        assert_eq!(progress(0, 100), "..........   0%");
+        assert_eq!(progress(50, 100), "#####.....  50%");
+        assert_eq!(progress(55, 100), "#####-....  55%");
+        assert_eq!(progress(100, 100), "########## 100%");
+    }
+
+    #[test]
+    fn progress_clamps_to_the_maximum() {
+        assert_eq!(progress(120, 100), "########## 120%");
+        assert_eq!(progress(255, 150), "#######+++ 255%");
+        // A maximum of 0 is taken as 1.
+        assert_eq!(progress(0, 0), "..........   0%");
+        assert_eq!(progress(1, 0), "##########   1%");
+    }
+
+    #[test]
+    fn progress_marks_cells_past_100() {
+        // 100% is at 6.67 cells, the seventh one is mostly below it.
+        assert_eq!(progress(100, 150), "######.... 100%");
+        assert_eq!(progress(120, 150), "#######+.. 120%");
+        assert_eq!(progress(150, 150), "#######+++ 150%");
+        // 100% is at 7.69 cells.
+        assert_eq!(progress(130, 130), "########++ 130%");
+        assert_eq!(progress(200, 200), "#####+++++ 200%");
+    }
+
+    /// Fails to read anything.
+    struct BrokenBackend;
+    impl MediaBackend for BrokenBackend {
+        fn get_volume_mute(&mut self) -> BackendResult<bool> {
+            Ok(false)
+        }
+        fn get_volume(&mut self) -> BackendResult<u8> {
+            Err("no sink".into())
+        }
+        fn inc_volume(&mut self, _inc: f32) -> BackendResult<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_readings_show_unavailable() {
+        let controller = MediaController {
+            action: Action::VolumeUp(5.0),
+            ..MediaController::default()
+        };
+        let mut app = MediaControllerApp {
+            backend: Box::new(BrokenBackend),
+            custom_controller: None,
+        };
+        let result = app.update(&controller);
+        assert_eq!(result.unwrap_err().to_string(), "no sink");
+
+        let state = OsdState::new(&controller, None);
+        assert_eq!(state.to_string(), "VOL: unavailable");
+        assert_eq!(state.level, None);
+        assert_eq!(state.icon.as_deref(), Some("audio-volume"));
+        for (action, label) in [
+            (Action::MicrophoneToggleMute, "MIC: unavailable"),
+            (Action::BrightnessSet(5.0), "BRT: unavailable"),
+            (Action::KeyboardToggle, "KBD: unavailable"),
+            (Action::PlayPause, "MEDIA: unavailable"),
+        ] {
+            let controller = MediaController {
+                action,
+                ..MediaController::default()
+            };
+            assert_eq!(OsdState::new(&controller, None).to_string(), label);
+        }
+
+        // The window owner gets the daemon's error instead of a reading.
+        let mut message = Message::new(MessageKind::Update);
+        message.action = Some(controller.action);
+        message.value = Some(50);
+        message.error = Some("no sink".to_string());
+        assert_eq!(Reading::from_message(&message), None);
+    }
+
+    fn reading(action: Action, value: Option<u8>, muted: bool) -> Reading {
+        Reading {
+            action,
+            value,
+            muted,
+            track: None,
+            device: None,
+        }
+    }
+
+    #[test]
+    fn icons_follow_the_level() {
+        let controller = MediaController {
+            max_volume: 150,
+            perceived_progress: false,
+            ..MediaController::default()
+        };
+        let volume = |value| reading(Action::VolumeUp(5.0), Some(value), false);
+        for (value, icon) in [
+            (0, "audio-volume-muted"),
+            (1, "audio-volume-low"),
+            (33, "audio-volume-low"),
+            (34, "audio-volume-medium"),
+            (66, "audio-volume-medium"),
+            (67, "audio-volume-high"),
+            (100, "audio-volume-high"),
+            (101, "audio-volume-overamplified"),
+            (150, "audio-volume-overamplified"),
+        ] {
+            assert_eq!(volume(value).icon_name(&controller), icon, "{value}");
+        }
+        let brightness = |value| reading(Action::BrightnessUp(5.0), Some(value), false);
+        for (value, icon) in [
+            (0, "display-brightness-low"),
+            (34, "display-brightness-medium"),
+            (100, "display-brightness-high"),
+        ] {
+            assert_eq!(brightness(value).icon_name(&controller), icon, "{value}");
+        }
+        let keyboard = reading(Action::KeyboardUp(5.0), Some(50), false);
+        assert_eq!(
+            keyboard.icon_name(&controller),
+            "keyboard-brightness-medium"
+        );
+        // Without a level, e.g. "MIC ON".
+        let microphone = reading(Action::MicrophoneToggleMute, None, false);
+        assert_eq!(
+            microphone.icon_name(&controller),
+            "microphone-sensitivity-high"
+        );
+    }
+
+    #[test]
+    fn icons_show_the_mute_state() {
+        let controller = MediaController::default();
+        for (action, icon) in [
+            (Action::VolumeToggleMute, "audio-volume-muted"),
+            (Action::VolumeUp(5.0), "audio-volume-muted"),
+            (Action::MicrophoneToggleMute, "microphone-sensitivity-muted"),
+        ] {
+            assert_eq!(reading(action, Some(80), true).icon_name(&controller), icon);
+            assert_eq!(reading(action, None, true).icon_name(&controller), icon);
+        }
+    }
+
+    #[test]
+    fn icons_show_the_playback_status() {
+        let controller = MediaController::default();
+        for (status, icon) in [
+            (PlaybackStatus::Playing, "media-playback-start"),
+            (PlaybackStatus::Paused, "media-playback-pause"),
+            (PlaybackStatus::Stopped, "media-playback-stop"),
+        ] {
+            let mut playback = reading(Action::PlayPause, None, false);
+            playback.track = Some(Track {
+                status,
+                ..Track::default()
+            });
+            assert_eq!(playback.icon_name(&controller), icon);
+        }
+    }
+}
@@ -1,108 +1,517 @@
+use media_controller::{
+    BackendError, BackendResult, BacklightBackend, DdcBackend, KeyboardBacklightBackend,
+    MediaBackend, MediaController, MprisBackend, Track,
+};
+
 const WPCTL: &str = "wpctl";
 const AUDIO_SINK: &str = "@DEFAULT_AUDIO_SINK@";
 const AUDIO_SOURCE: &str = "@DEFAULT_AUDIO_SOURCE@";
+const SINKS: &str = "Sinks";
+const SOURCES: &str = "Sources";
+
+/// Delegates audio operations to `audio`, brightness operations to
+/// `brightness` (or `ddc` when outputs are selected for it), keyboard
+/// backlight operations to `keyboard` and playback operations to `media`.
+struct DefaultBackend {
+    audio: Box<dyn MediaBackend>,
+    brightness: Box<dyn MediaBackend>,
+    ddc: Box<dyn MediaBackend>,
+    keyboard: Box<dyn MediaBackend>,
+    media: Box<dyn MediaBackend>,
+    use_ddc: bool,
+}
+impl DefaultBackend {
+    fn brightness(&mut self) -> &mut dyn MediaBackend {
+        if self.use_ddc {
+            self.ddc.as_mut()
+        } else {
+            self.brightness.as_mut()
+        }
+    }
+}
+impl MediaBackend for DefaultBackend {
+    fn configure(&mut self, controller: &MediaController) -> BackendResult<()> {
+        self.use_ddc = !controller.ddc.is_empty();
+        self.audio.configure(controller)?;
+        self.brightness().configure(controller)?;
+        self.keyboard.configure(controller)?;
+        self.media.configure(controller)
+    }
+    fn get_volume_mute(&mut self) -> BackendResult<bool> {
+        self.audio.get_volume_mute()
+    }
+    fn get_microphone_mute(&mut self) -> BackendResult<bool> {
+        self.audio.get_microphone_mute()
+    }
+    fn get_volume(&mut self) -> BackendResult<u8> {
+        self.audio.get_volume()
+    }
+    fn get_microphone_volume(&mut self) -> BackendResult<u8> {
+        self.audio.get_microphone_volume()
+    }
+    fn get_brightness(&mut self) -> BackendResult<u8> {
+        self.brightness().get_brightness()
+    }
+    fn inc_volume(&mut self, inc: f32) -> BackendResult<()> {
+        self.audio.inc_volume(inc)
+    }
+    fn inc_microphone_volume(&mut self, inc: f32) -> BackendResult<()> {
+        self.audio.inc_microphone_volume(inc)
+    }
+    fn inc_brightness(&mut self, inc: f32) -> BackendResult<()> {
+        self.brightness().inc_brightness(inc)
+    }
+    fn set_volume(&mut self, volume: f32) -> BackendResult<()> {
+        self.audio.set_volume(volume)
+    }
+    fn set_microphone_volume(&mut self, volume: f32) -> BackendResult<()> {
+        self.audio.set_microphone_volume(volume)
+    }
+    fn set_brightness(&mut self, brightness: f32) -> BackendResult<()> {
+        self.brightness().set_brightness(brightness)
+    }
+    fn toggle_volume_mute(&mut self) -> BackendResult<()> {
+        self.audio.toggle_volume_mute()
+    }
+    fn toggle_microphone_mute(&mut self) -> BackendResult<()> {
+        self.audio.toggle_microphone_mute()
+    }
+    fn set_volume_mute(&mut self, mute: bool) -> BackendResult<()> {
+        self.audio.set_volume_mute(mute)
+    }
+    fn set_microphone_mute(&mut self, mute: bool) -> BackendResult<()> {
+        self.audio.set_microphone_mute(mute)
+    }
+    fn get_keyboard_brightness(&mut self) -> BackendResult<u8> {
+        self.keyboard.get_keyboard_brightness()
+    }
+    fn inc_keyboard_brightness(&mut self, inc: f32) -> BackendResult<()> {
+        self.keyboard.inc_keyboard_brightness(inc)
+    }
+    fn set_keyboard_brightness(&mut self, brightness: f32) -> BackendResult<()> {
+        self.keyboard.set_keyboard_brightness(brightness)
+    }
+    fn toggle_keyboard_backlight(&mut self) -> BackendResult<()> {
+        self.keyboard.toggle_keyboard_backlight()
+    }
+    fn get_volume_device(&mut self) -> BackendResult<String> {
+        self.audio.get_volume_device()
+    }
+    fn get_microphone_device(&mut self) -> BackendResult<String> {
+        self.audio.get_microphone_device()
+    }
+    fn next_volume_device(&mut self) -> BackendResult<()> {
+        self.audio.next_volume_device()
+    }
+    fn get_track(&mut self) -> BackendResult<Track> {
+        self.media.get_track()
+    }
+    fn play_pause(&mut self) -> BackendResult<()> {
+        self.media.play_pause()
+    }
+    fn next_track(&mut self) -> BackendResult<()> {
+        self.media.next_track()
+    }
+    fn previous_track(&mut self) -> BackendResult<()> {
+        self.media.previous_track()
+    }
+}
+
+/// Uses `wpctl` for audio.
+#[derive(Default)]
+struct WpctlBackend {
+    /// Node IDs of the sink and source to control, `None` for the default
+    /// ones.
+    sink: Option<String>,
+    source: Option<String>,
+    /// Highest sink volume in %.
+    max_volume: u8,
+}
+impl WpctlBackend {
+    fn sink(&self) -> &str {
+        self.sink.as_deref().unwrap_or(AUDIO_SINK)
+    }
+    fn source(&self) -> &str {
+        self.source.as_deref().unwrap_or(AUDIO_SOURCE)
+    }
+    fn max_volume(&self) -> u8 {
+        self.max_volume.max(100)
+    }
+}
+impl MediaBackend for WpctlBackend {
+    fn configure(&mut self, controller: &MediaController) -> BackendResult<()> {
+        self.sink = controller
+            .sink()
+            .map(|name| find_node(SINKS, name))
+            .transpose()?;
+        self.source = controller
+            .source()
+            .map(|name| find_node(SOURCES, name))
+            .transpose()?;
+        self.max_volume = controller.max_volume;
+        Ok(())
+    }
+    fn get_volume_mute(&mut self) -> BackendResult<bool> {
+        get_mute(self.sink())
+    }
+    fn get_microphone_mute(&mut self) -> BackendResult<bool> {
+        get_mute(self.source())
+    }
+    fn get_volume(&mut self) -> BackendResult<u8> {
+        get_volume(self.sink())
+    }
+    fn get_microphone_volume(&mut self) -> BackendResult<u8> {
+        get_volume(self.source())
+    }
+    fn inc_volume(&mut self, inc: f32) -> BackendResult<()> {
+        inc_volume(self.sink(), inc, self.max_volume())
+    }
+    fn inc_microphone_volume(&mut self, inc: f32) -> BackendResult<()> {
+        inc_volume(self.source(), inc, 100)
+    }
+    fn set_volume(&mut self, volume: f32) -> BackendResult<()> {
+        set_volume(self.sink(), volume.min(self.max_volume() as f32))
+    }
+    fn set_microphone_volume(&mut self, volume: f32) -> BackendResult<()> {
+        set_volume(self.source(), volume)
+    }
+    fn toggle_volume_mute(&mut self) -> BackendResult<()> {
+        toggle_mute(self.sink())
+    }
+    fn toggle_microphone_mute(&mut self) -> BackendResult<()> {
+        toggle_mute(self.source())
+    }
+    fn set_volume_mute(&mut self, mute: bool) -> BackendResult<()> {
+        force_mute(self.sink(), mute)
+    }
+    fn set_microphone_mute(&mut self, mute: bool) -> BackendResult<()> {
+        force_mute(self.source(), mute)
+    }
+    fn get_volume_device(&mut self) -> BackendResult<String> {
+        get_description(self.sink())
+    }
+    fn get_microphone_device(&mut self) -> BackendResult<String> {
+        get_description(self.source())
+    }
+    fn next_volume_device(&mut self) -> BackendResult<()> {
+        let sinks = list_nodes(SINKS, false)?;
+        let next = match sinks.iter().position(|sink| sink.default) {
+            Some(i) => (i + 1) % sinks.len(),
+            None => 0,
+        };
+        let id = sinks.get(next).ok_or("no sinks found")?.id.to_string();
+        run_command(WPCTL, &["set-default", &id])?;
+        Ok(())
+    }
+}
 
-const BRIGHTNESSCTL: &str = "brightnessctl";
+/// Prefers talking to the sound server directly, falls back to `wpctl` when
+/// its socket can't be found.
+#[cfg(feature = "pulse")]
+fn audio_backend() -> Box<dyn MediaBackend> {
+    let pulse = media_controller::PulseBackend::new();
+    if pulse.socket_path().exists() {
+        return Box::new(pulse);
+    }
+    Box::new(WpctlBackend::default())
+}
+
+#[cfg(not(feature = "pulse"))]
+fn audio_backend() -> Box<dyn MediaBackend> {
+    Box::new(WpctlBackend::default())
+}
 
 fn main() {
-    media_controller::MediaControllerApp {
-        toggle_volume_mute,
-        get_volume_mute,
-        toggle_microphone_mute,
-        get_microphone_mute,
-        get_volume,
-        get_brightness,
-        inc_volume,
-        inc_brightness,
+    let result = media_controller::MediaControllerApp {
+        backend: Box::new(DefaultBackend {
+            audio: audio_backend(),
+            brightness: Box::new(BacklightBackend::new()),
+            ddc: Box::new(DdcBackend::new()),
+            keyboard: Box::new(KeyboardBacklightBackend::new()),
+            media: Box::new(MprisBackend::new()),
+            use_ddc: false,
+        }),
         custom_controller: None,
     }
     .run();
+    // The error has already been reported by `run`.
+    if result.is_err() {
+        std::process::exit(1);
+    }
 }
 
-fn run_get_volume_output() -> String {
-    let stdout = std::process::Command::new(WPCTL)
-        .args(["get-volume", AUDIO_SINK])
+fn run_command(program: &str, args: &[&str]) -> BackendResult<String> {
+    let output = std::process::Command::new(program)
+        .args(args)
         .output()
-        .unwrap()
-        .stdout;
-    String::from_utf8(stdout).unwrap()
+        .map_err(|err| format!("failed to run {program}: {err}"))?;
+    if !output.status.success() {
+        return Err(format!("{program} exited with {}", output.status).into());
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|err| BackendError::from(format!("invalid output from {program}: {err}")))
 }
 
-fn run_get_microphone_volume_output() -> String {
-    let stdout = std::process::Command::new(WPCTL)
-        .args(["get-volume", AUDIO_SOURCE])
-        .output()
-        .unwrap()
-        .stdout;
-    String::from_utf8(stdout).unwrap()
+fn get_formatted_value(value: f32) -> String {
+    format!("{}%{}", value.abs(), if value < 0.0 { '-' } else { '+' })
 }
 
-fn get_formatted_value(value: i8) -> String {
-    format!("{}%{}", value.abs(), if value < 0 { '-' } else { '+' })
+fn get_mute(node: &str) -> BackendResult<bool> {
+    Ok(run_command(WPCTL, &["get-volume", node])?.contains("MUTED"))
 }
 
-fn get_volume_mute() -> bool {
-    run_get_volume_output().contains("MUTED")
+fn get_volume(node: &str) -> BackendResult<u8> {
+    let output = run_command(WPCTL, &["get-volume", node])?;
+    let f32_vol = output
+        .split(' ')
+        .nth(1)
+        .and_then(|volume| volume.trim().parse::<f32>().ok())
+        .ok_or_else(|| format!("unexpected output from {WPCTL}: {}", output.trim()))?
+        * 100.0;
+    Ok(f32_vol.round() as u8)
 }
 
-fn get_microphone_mute() -> bool {
-    run_get_microphone_volume_output().contains("MUTED")
+fn force_mute(node: &str, mute: bool) -> BackendResult<()> {
+    run_command(WPCTL, &["set-mute", node, if mute { "1" } else { "0" }])?;
+    Ok(())
 }
 
-fn get_volume() -> u8 {
-    let f32_vol = run_get_volume_output()
-        .split(' ')
-        .nth(1)
-        .unwrap()
-        .trim()
-        .parse::<f32>()
-        .unwrap()
-        * 100.0;
-    f32_vol.round() as u8
+fn toggle_mute(node: &str) -> BackendResult<()> {
+    run_command(WPCTL, &["set-mute", node, "toggle"])?;
+    Ok(())
 }
 
-fn force_mute(mute: bool) {
-    std::process::Command::new(WPCTL)
-        .args(["set-mute", AUDIO_SINK, if mute { "1" } else { "0" }])
-        .output()
-        .unwrap();
+/// `max` is the highest volume in %.
+fn inc_volume(node: &str, inc: f32, max: u8) -> BackendResult<()> {
+    force_mute(node, false)?;
+    if inc > 0.0 && get_volume(node)? >= max {
+        return Ok(());
+    }
+    let limit = format!("{:.2}", max as f32 / 100.0);
+    run_command(
+        WPCTL,
+        &["set-volume", "-l", &limit, node, &get_formatted_value(inc)],
+    )?;
+    Ok(())
 }
 
-fn toggle_volume_mute() {
-    std::process::Command::new(WPCTL)
-        .args(["set-mute", AUDIO_SINK, "toggle"])
-        .output()
-        .unwrap();
+fn set_volume(node: &str, volume: f32) -> BackendResult<()> {
+    force_mute(node, false)?;
+    run_command(
+        WPCTL,
+        &["set-volume", node, &format!("{}%", volume.max(0.0))],
+    )?;
+    Ok(())
 }
 
-fn toggle_microphone_mute() {
-    std::process::Command::new(WPCTL)
-        .args(["set-mute", AUDIO_SOURCE, "toggle"])
-        .output()
-        .unwrap();
+/// A node listed by `wpctl status`.
+#[derive(Debug, PartialEq)]
+struct Node {
+    id: u32,
+    label: String,
+    default: bool,
 }
 
-fn inc_volume(inc: i8) {
-    force_mute(false);
-    if inc > 0 && get_volume() >= 100 {
-        return;
+/// Lists the nodes under `section` in the audio part of `wpctl status`, with
+/// their description as the label, or their name if `names` is true.
+fn list_nodes(section: &str, names: bool) -> BackendResult<Vec<Node>> {
+    let args: &[&str] = if names {
+        &["status", "--name"]
+    } else {
+        &["status"]
+    };
+    Ok(parse_nodes(&run_command(WPCTL, args)?, section))
+}
+
+/// Parses the nodes under `section` in the audio part of `wpctl status`'s
+/// output.
+fn parse_nodes(output: &str, section: &str) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let mut in_audio = false;
+    let mut in_section = false;
+    for line in output.lines() {
+        // Top-level headings, e.g. `Audio` and `Video`, aren't indented.
+        if !line.starts_with(char::is_whitespace) {
+            in_audio = line.trim() == "Audio";
+            in_section = false;
+            continue;
+        }
+        // e.g. ` │  *   46. Built-in Audio Analog Stereo [vol: 0.40]`
+        let entry = line.trim_start_matches(|c: char| c.is_whitespace() || "│├└─".contains(c));
+        let (default, node) = match entry.strip_prefix('*') {
+            Some(node) => (true, node.trim_start()),
+            None => (false, entry),
+        };
+        if let Some((id, label)) = node.split_once(". ") {
+            if let (true, Ok(id)) = (in_section, id.parse()) {
+                let label = label.split(" [").next().unwrap_or(label).trim();
+                nodes.push(Node {
+                    id,
+                    label: label.to_string(),
+                    default,
+                });
+                continue;
+            }
+        }
+        if let Some(heading) = entry.strip_suffix(':') {
+            in_section = in_audio && heading == section;
+        }
     }
-    std::process::Command::new(WPCTL)
-        .args(["set-volume", AUDIO_SINK, &get_formatted_value(inc)])
-        .output()
-        .unwrap();
+    nodes
 }
-fn get_brightness() -> u8 {
-    std::fs::read_to_string("/sys/class/backlight/nvidia_0/brightness")
-        .unwrap()
-        .trim()
-        .parse()
-        .unwrap()
+
+/// Returns the ID of the node called `name` under `section`. IDs are used as
+/// they are.
+fn find_node(section: &str, name: &str) -> BackendResult<String> {
+    if name.parse::<u32>().is_ok() {
+        return Ok(name.to_string());
+    }
+    let nodes = list_nodes(section, true)?;
+    nodes
+        .iter()
+        .find(|node| node.label == name)
+        .map(|node| node.id.to_string())
+        .ok_or_else(|| {
+            let available = nodes
+                .iter()
+                .map(|node| node.label.as_str())
+                .collect::<Vec<_>>();
+            format!(
+                "device {name} not found (available: {})",
+                available.join(", ")
+            )
+            .into()
+        })
 }
 
-fn inc_brightness(inc: i8) {
-    std::process::Command::new(BRIGHTNESSCTL)
-        .args(["s", &get_formatted_value(inc)])
-        .output()
-        .unwrap();
+fn get_description(node: &str) -> BackendResult<String> {
+    let output = run_command(WPCTL, &["inspect", node])?;
+    parse_description(&output)
+        .ok_or_else(|| format!("{WPCTL} inspect {node}: no node.description").into())
+}
+
+fn parse_description(output: &str) -> Option<String> {
+    // e.g. `  * node.description = "Built-in Audio Analog Stereo"`
+    output.lines().find_map(|line| {
+        let value = line
+            .trim_start_matches([' ', '*'])
+            .strip_prefix("node.description = ")?;
+        Some(value.trim_matches('"').to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `wpctl status`
+    const STATUS: &str = "\
+PipeWire 'pipewire-0' [1.0.5, user@host, cookie:1163266174]
+ └─ Clients:
+        33. WirePlumber                         [1.0.5, user@host, pid:1021]
+
+Audio
+ ├─ Devices:
+ │      42. Built-in Audio                      [alsa]
+ │  
+ ├─ Sinks:
+ │      46. Built-in Audio Analog Stereo        [vol: 0.40]
+ │  *   51. HDMI / DisplayPort 3 Output         [vol: 1.00 MUTED]
+ │  
+ ├─ Sink endpoints:
+ │  
+ ├─ Sources:
+ │  *   47. Built-in Audio Analog Stereo        [vol: 1.00]
+ │  
+ ├─ Source endpoints:
+ │  
+ └─ Streams:
+        60. Firefox
+             61. output_FL       > Built-in Audio:playback_FL	[active]
+
+Video
+ ├─ Devices:
+ │      40. Integrated Camera                   [v4l2]
+ │  
+ ├─ Sinks:
+ │  
+ ├─ Sources:
+ │  *   55. Integrated Camera (V4L2)
+ │  
+ └─ Streams:
+
+Settings
+ └─ Default Configured Node Names:
+         0. Audio/Sink    alsa_output.pci-0000_00_1f.3.hdmi-stereo
+";
+
+    /// `wpctl inspect 46`
+    const INSPECT: &str = r#"id 46, type PipeWire:Interface:Node
+    alsa.card = "0"
+    device.id = "42"
+  * factory.id = "19"
+  * media.class = "Audio/Sink"
+  * node.description = "Built-in Audio Analog Stereo"
+  * node.name = "alsa_output.pci-0000_00_1f.3.analog-stereo"
+"#;
+
+    fn node(id: u32, label: &str, default: bool) -> Node {
+        Node {
+            id,
+            label: label.to_string(),
+            default,
+        }
+    }
+
+    #[test]
+    fn parses_audio_sinks() {
+        assert_eq!(
+            parse_nodes(STATUS, "Sinks"),
+            [
+                node(46, "Built-in Audio Analog Stereo", false),
+                node(51, "HDMI / DisplayPort 3 Output", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_audio_sources_only() {
+        assert_eq!(
+            parse_nodes(STATUS, "Sources"),
+            [node(47, "Built-in Audio Analog Stereo", true)]
+        );
+        assert_eq!(parse_nodes(STATUS, "Sink endpoints"), []);
+        assert_eq!(parse_nodes(STATUS, "Clients"), []);
+    }
+
+    #[test]
+    fn parses_names() {
+        let status = "\
+Audio
+ ├─ Sinks:
+ │  *   46. alsa_output.pci-0000_00_1f.3.analog-stereo [vol: 0.40]
+ │      51. alsa_output.pci-0000_00_1f.3.hdmi-stereo [vol: 1.00 MUTED]
+ │  
+ └─ Streams:
+";
+        assert_eq!(
+            parse_nodes(status, "Sinks"),
+            [
+                node(46, "alsa_output.pci-0000_00_1f.3.analog-stereo", true),
+                node(51, "alsa_output.pci-0000_00_1f.3.hdmi-stereo", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_the_description() {
+        assert_eq!(
+            parse_description(INSPECT).as_deref(),
+            Some("Built-in Audio Analog Stereo")
+        );
+        assert_eq!(
+            parse_description("id 46, type PipeWire:Interface:Node\n"),
+            None
+        );
+    }
 }
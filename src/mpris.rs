@@ -0,0 +1,694 @@
+//! Media players implementing MPRIS, controlled over the session bus with
+//! `busctl`.
+//!
+//! The bus is reached through a `SessionBus`, so that the backend can be
+//! driven by a fake one instead of actual players.
+
+use std::time::{Duration, Instant};
+
+use crate::{BackendError, BackendResult, MediaBackend, MediaController, PlaybackStatus, Track};
+
+const BUSCTL: &str = "busctl";
+const BUS_NAME_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+/// Players apply commands asynchronously, this is how long to wait for the
+/// change to show up before reporting the state anyway.
+const SETTLE_TIMEOUT: Duration = Duration::from_millis(500);
+const SETTLE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Access to the session bus, as `busctl --user`.
+pub trait SessionBus {
+    /// Runs `busctl --user` with `args` and returns its output.
+    fn busctl(&self, args: &[&str]) -> BackendResult<String>;
+}
+
+/// The actual session bus, at `$DBUS_SESSION_BUS_ADDRESS`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Busctl;
+impl SessionBus for Busctl {
+    fn busctl(&self, args: &[&str]) -> BackendResult<String> {
+        let output = std::process::Command::new(BUSCTL)
+            .arg("--user")
+            .args(args)
+            .output()
+            .map_err(|err| format!("failed to run {BUSCTL}: {err}"))?;
+        if !output.status.success() {
+            return Err(BackendError::from(format!(
+                "{BUSCTL} {}: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        String::from_utf8(output.stdout)
+            .map_err(|err| format!("invalid output from {BUSCTL}: {err}").into())
+    }
+}
+
+/// Controls a media player through its `org.mpris.MediaPlayer2.*` bus name.
+pub struct MprisBackend<B = Busctl> {
+    bus: B,
+    player: Option<String>,
+    /// The player the last command went to. Its track is the one shown, even
+    /// if another player would be picked by now.
+    commanded: Option<String>,
+}
+impl MprisBackend {
+    pub fn new() -> Self {
+        Self::with_bus(Busctl)
+    }
+}
+impl<B: SessionBus> MprisBackend<B> {
+    /// Talks to the players through `bus`.
+    pub fn with_bus(bus: B) -> Self {
+        Self {
+            bus,
+            player: None,
+            commanded: None,
+        }
+    }
+    /// Picks the player to control, e.g. `spotify`. `None` prefers the one
+    /// that is playing.
+    pub fn set_player(&mut self, player: Option<String>) {
+        self.player = player;
+    }
+    /// Returns the names of the running players, without the
+    /// `org.mpris.MediaPlayer2.` prefix, sorted.
+    pub fn players(&self) -> BackendResult<Vec<String>> {
+        let output = self.bus.busctl(&[
+            "call",
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "ListNames",
+        ])?;
+        let mut players = parse_output(&output)?
+            .iter()
+            .flat_map(Value::items)
+            .filter_map(|name| name.as_str()?.strip_prefix(BUS_NAME_PREFIX))
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        players.sort();
+        Ok(players)
+    }
+    fn bus_name(&self) -> BackendResult<String> {
+        let players = self.players()?;
+        if let Some(player) = &self.player {
+            // Players running several instances append a suffix, e.g.
+            // `firefox.instance_1_42`.
+            return players
+                .iter()
+                .find(|name| *name == player || name.starts_with(&format!("{player}.")))
+                .map(|name| format!("{BUS_NAME_PREFIX}{name}"))
+                .ok_or_else(|| {
+                    format!(
+                        "player {player} not found (available: {})",
+                        players.join(", ")
+                    )
+                    .into()
+                });
+        }
+        let bus_names = players
+            .iter()
+            .map(|name| format!("{BUS_NAME_PREFIX}{name}"))
+            .collect::<Vec<_>>();
+        if bus_names.len() <= 1 {
+            return bus_names
+                .into_iter()
+                .next()
+                .ok_or_else(|| "no MPRIS player found".into());
+        }
+        // A playing player comes first, then a paused one. Statuses are only
+        // read until a playing one shows up.
+        let mut paused = None;
+        for bus_name in &bus_names {
+            match self.status(bus_name) {
+                Ok(PlaybackStatus::Playing) => return Ok(bus_name.clone()),
+                Ok(PlaybackStatus::Paused) => {
+                    paused.get_or_insert(bus_name);
+                }
+                _ => {}
+            }
+        }
+        Ok(paused.unwrap_or(&bus_names[0]).clone())
+    }
+    /// Calls `method` and waits until `property` changes, or
+    /// `SETTLE_TIMEOUT` runs out.
+    fn command(&mut self, method: &str, property: &str) -> BackendResult<()> {
+        let bus_name = self.bus_name()?;
+        let before = self.get_property(&bus_name, property)?;
+        self.bus
+            .busctl(&["call", &bus_name, OBJECT_PATH, PLAYER_INTERFACE, method])?;
+        let start = Instant::now();
+        while start.elapsed() < SETTLE_TIMEOUT {
+            if self.get_property(&bus_name, property)? != before {
+                break;
+            }
+            std::thread::sleep(SETTLE_POLL_INTERVAL);
+        }
+        self.commanded = Some(bus_name);
+        Ok(())
+    }
+    fn get_property(&self, bus_name: &str, property: &str) -> BackendResult<Value> {
+        let output = self.bus.busctl(&[
+            "get-property",
+            bus_name,
+            OBJECT_PATH,
+            PLAYER_INTERFACE,
+            property,
+        ])?;
+        parse_output(&output)?
+            .pop()
+            .ok_or_else(|| format!("{bus_name} returned no {property}").into())
+    }
+    fn status(&self, bus_name: &str) -> BackendResult<PlaybackStatus> {
+        let status = self.get_property(bus_name, "PlaybackStatus")?;
+        status
+            .as_str()
+            .and_then(PlaybackStatus::parse)
+            .ok_or_else(|| format!("{bus_name} reported an invalid PlaybackStatus").into())
+    }
+    fn track(&self, bus_name: &str) -> BackendResult<Track> {
+        let metadata = self.get_property(bus_name, "Metadata")?;
+        let title = metadata
+            .get("xesam:title")
+            .and_then(Value::as_str)
+            .filter(|title| !title.is_empty())
+            .map(str::to_string);
+        let artists = metadata
+            .get("xesam:artist")
+            .map(Value::items)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>();
+        Ok(Track {
+            title,
+            artist: (!artists.is_empty()).then(|| artists.join(", ")),
+            status: self.status(bus_name)?,
+        })
+    }
+}
+impl std::default::Default for MprisBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<B: SessionBus + Send> MediaBackend for MprisBackend<B> {
+    fn configure(&mut self, controller: &MediaController) -> BackendResult<()> {
+        self.set_player(controller.player.clone());
+        self.commanded = None;
+        Ok(())
+    }
+    fn get_track(&mut self) -> BackendResult<Track> {
+        let bus_name = match self.commanded.take() {
+            Some(bus_name) => bus_name,
+            None => self.bus_name()?,
+        };
+        self.track(&bus_name)
+    }
+    fn play_pause(&mut self) -> BackendResult<()> {
+        self.command("PlayPause", "PlaybackStatus")
+    }
+    fn next_track(&mut self) -> BackendResult<()> {
+        self.command("Next", "Metadata")
+    }
+    fn previous_track(&mut self) -> BackendResult<()> {
+        self.command("Previous", "Metadata")
+    }
+}
+
+/// A D-Bus value as printed by `busctl`. Variants are replaced by their
+/// content.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Str(String),
+    /// Numbers and booleans, as printed.
+    Word(String),
+    /// Arrays and structs.
+    List(Vec<Value>),
+    Dict(Vec<(Value, Value)>),
+}
+impl Value {
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+    fn items(&self) -> &[Value] {
+        match self {
+            Self::List(items) => items,
+            _ => &[],
+        }
+    }
+    fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Self::Dict(entries) => entries
+                .iter()
+                .find(|(k, _)| k.as_str() == Some(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Str(String),
+    Word(String),
+}
+
+/// Parses `busctl`'s output, e.g. `as 2 "a" "b"`: the signature followed by
+/// the values.
+fn parse_output(output: &str) -> BackendResult<Vec<Value>> {
+    let invalid = || BackendError::from(format!("unexpected output from {BUSCTL}: {output}"));
+    let mut tokens = tokenize(output).ok_or_else(invalid)?.into_iter();
+    let signature = match tokens.next() {
+        Some(Token::Word(signature)) => signature,
+        None => return Ok(Vec::new()),
+        _ => return Err(invalid()),
+    };
+    let mut values = Vec::new();
+    let mut rest = signature.as_str();
+    while !rest.is_empty() {
+        let (single, remaining) = split_type(rest).ok_or_else(invalid)?;
+        values.push(parse_value(single, &mut tokens).ok_or_else(invalid)?);
+        rest = remaining;
+    }
+    Ok(values)
+}
+
+fn parse_value(signature: &str, tokens: &mut impl Iterator<Item = Token>) -> Option<Value> {
+    match signature.as_bytes()[0] {
+        b's' | b'o' | b'g' => match tokens.next()? {
+            Token::Str(s) => Some(Value::Str(s)),
+            Token::Word(_) => None,
+        },
+        b'v' => match tokens.next()? {
+            Token::Word(inner) => parse_value(split_type(&inner)?.0, tokens),
+            Token::Str(_) => None,
+        },
+        b'a' => {
+            let count = match tokens.next()? {
+                Token::Word(count) => count.parse::<usize>().ok()?,
+                Token::Str(_) => return None,
+            };
+            let element = &signature[1..];
+            if let Some(entry) = element.strip_prefix('{') {
+                let (key, value) = split_type(entry)?;
+                let value = split_type(value)?.0;
+                let entries = (0..count)
+                    .map(|_| Some((parse_value(key, tokens)?, parse_value(value, tokens)?)))
+                    .collect::<Option<_>>()?;
+                return Some(Value::Dict(entries));
+            }
+            let items = (0..count)
+                .map(|_| parse_value(element, tokens))
+                .collect::<Option<_>>()?;
+            Some(Value::List(items))
+        }
+        b'(' => {
+            let mut fields = Vec::new();
+            let mut rest = &signature[1..signature.len() - 1];
+            while !rest.is_empty() {
+                let (single, remaining) = split_type(rest)?;
+                fields.push(parse_value(single, tokens)?);
+                rest = remaining;
+            }
+            Some(Value::List(fields))
+        }
+        _ => match tokens.next()? {
+            Token::Word(word) => Some(Value::Word(word)),
+            Token::Str(_) => None,
+        },
+    }
+}
+
+/// Splits the first complete type off `signature`, e.g. `a{sv}s` into `a{sv}`
+/// and `s`.
+fn split_type(signature: &str) -> Option<(&str, &str)> {
+    let mut depth = 0;
+    for (i, c) in signature.char_indices() {
+        match c {
+            'a' => continue,
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            return Some(signature.split_at(i + 1));
+        }
+    }
+    None
+}
+
+/// Splits the output into words and quoted strings. `busctl` escapes
+/// non-ASCII bytes in strings as octal sequences.
+fn tokenize(output: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = output.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c != '"' {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                word.push(c);
+            }
+            tokens.push(Token::Word(word));
+            continue;
+        }
+        chars.next();
+        let mut bytes = Vec::new();
+        loop {
+            match chars.next()? {
+                '"' => break,
+                '\\' => {
+                    let escaped = chars.next()?;
+                    let byte = match escaped {
+                        'n' => b'\n',
+                        't' => b'\t',
+                        'r' => b'\r',
+                        'x' => {
+                            let hex = [chars.next()?, chars.next()?].iter().collect::<String>();
+                            u8::from_str_radix(&hex, 16).ok()?
+                        }
+                        '0'..='7' => {
+                            let octal = [escaped, chars.next()?, chars.next()?]
+                                .iter()
+                                .collect::<String>();
+                            u8::from_str_radix(&octal, 8).ok()?
+                        }
+                        c if c.is_ascii() => c as u8,
+                        _ => return None,
+                    };
+                    bytes.push(byte);
+                }
+                c => bytes.extend(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            }
+        }
+        tokens.push(Token::Str(String::from_utf8_lossy(&bytes).into_owned()));
+    }
+    Some(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// `busctl --user get-property org.mpris.MediaPlayer2.spotify
+    /// /org/mpris/MediaPlayer2 org.mpris.MediaPlayer2.Player Metadata`
+    const METADATA: &str = concat!(
+        r#"a{sv} 6 "mpris:trackid" o "/com/spotify/track/4uLU6hMCjMI75M1A2tKUQC" "#,
+        r#""mpris:length" x 254000000 "xesam:album" s "Debut" "#,
+        r#""xesam:artist" as 2 "Bj\303\266rk" "Guy \"Sigsworth\"" "#,
+        r#""xesam:title" s "Human Behaviour" "xesam:trackNumber" i 1"#,
+        "\n"
+    );
+
+    fn str(s: &str) -> Value {
+        Value::Str(s.to_string())
+    }
+
+    #[test]
+    fn tokenizes_strings_and_escapes() {
+        assert_eq!(
+            tokenize(r#"as 2 "Bj\303\266rk" "a\tb \"c\" \x41\\""#).unwrap(),
+            [
+                Token::Word("as".to_string()),
+                Token::Word("2".to_string()),
+                Token::Str("Björk".to_string()),
+                Token::Str("a\tb \"c\" A\\".to_string()),
+            ]
+        );
+        assert_eq!(tokenize(r#"s "unterminated"#), None);
+        assert_eq!(tokenize(r#"s "\3""#), None);
+    }
+
+    #[test]
+    fn splits_complete_types() {
+        assert_eq!(split_type("a{sv}s"), Some(("a{sv}", "s")));
+        assert_eq!(split_type("(sa(ii))x"), Some(("(sa(ii))", "x")));
+        assert_eq!(split_type("aas"), Some(("aas", "")));
+        assert_eq!(split_type("a{sv"), None);
+    }
+
+    #[test]
+    fn parses_metadata() {
+        let values = parse_output(METADATA).unwrap();
+        assert_eq!(values.len(), 1);
+        let metadata = &values[0];
+        assert_eq!(
+            metadata.get("mpris:trackid"),
+            Some(&str("/com/spotify/track/4uLU6hMCjMI75M1A2tKUQC"))
+        );
+        assert_eq!(
+            metadata.get("mpris:length"),
+            Some(&Value::Word("254000000".to_string()))
+        );
+        assert_eq!(
+            metadata.get("xesam:artist"),
+            Some(&Value::List(vec![str("Björk"), str("Guy \"Sigsworth\"")]))
+        );
+        assert_eq!(metadata.get("xesam:title"), Some(&str("Human Behaviour")));
+        assert_eq!(
+            metadata.get("xesam:trackNumber"),
+            Some(&Value::Word("1".to_string()))
+        );
+        assert_eq!(metadata.get("xesam:url"), None);
+    }
+
+    #[test]
+    fn parses_several_values_and_structs() {
+        assert_eq!(
+            parse_output(r#"sa(si) "x" 2 "a" 1 "b" -2"#).unwrap(),
+            [
+                str("x"),
+                Value::List(vec![
+                    Value::List(vec![str("a"), Value::Word("1".to_string())]),
+                    Value::List(vec![str("b"), Value::Word("-2".to_string())]),
+                ]),
+            ]
+        );
+        assert_eq!(
+            parse_output(r#"as 3 "org.freedesktop.DBus" ":1.42" "org.mpris.MediaPlayer2.mpv""#)
+                .unwrap()[0]
+                .items()
+                .len(),
+            3
+        );
+        assert_eq!(parse_output("\n").unwrap(), []);
+    }
+
+    #[test]
+    fn rejects_unexpected_output() {
+        for output in [
+            r#""s" "x""#,
+            "s 1",
+            r#"as 2 "a""#,
+            r#"a{sv} 1 "k" v "x""#,
+            r#"x "1""#,
+        ] {
+            let err = parse_output(output).unwrap_err();
+            assert!(
+                err.to_string().starts_with("unexpected output from busctl"),
+                "{output}: {err}"
+            );
+        }
+    }
+
+    /// A session bus with fake players, each given as its name, status and
+    /// title. Commands show up after `delay` reads of a property, as players
+    /// apply them asynchronously.
+    struct FakeBus(Mutex<FakeState>);
+    struct FakeState {
+        players: Vec<(String, String, String)>,
+        delay: usize,
+        /// Reads left before the pending command shows up, its bus name and
+        /// method.
+        pending: Option<(usize, String, String)>,
+        calls: Vec<String>,
+    }
+    impl FakeState {
+        fn apply(&mut self, bus_name: &str, method: &str) {
+            let (_, status, title) = self
+                .players
+                .iter_mut()
+                .find(|(name, ..)| format!("{BUS_NAME_PREFIX}{name}") == bus_name)
+                .unwrap();
+            match method {
+                "PlayPause" if status == "Playing" => *status = "Paused".to_string(),
+                "PlayPause" => *status = "Playing".to_string(),
+                _ => title.push_str(" (next)"),
+            }
+        }
+    }
+    impl SessionBus for FakeBus {
+        fn busctl(&self, args: &[&str]) -> BackendResult<String> {
+            let mut state = self.0.lock().unwrap();
+            state.calls.push(args.join(" "));
+            match args {
+                ["call", "org.freedesktop.DBus", .., "ListNames"] => {
+                    let names = state
+                        .players
+                        .iter()
+                        .map(|(name, ..)| format!(" \"{BUS_NAME_PREFIX}{name}\""))
+                        .collect::<String>();
+                    Ok(format!("as {} \":1.7\"{names}\n", state.players.len() + 1))
+                }
+                ["call", bus_name, .., method] => {
+                    match state.delay {
+                        0 => state.apply(bus_name, method),
+                        delay => {
+                            state.pending = Some((delay, bus_name.to_string(), method.to_string()))
+                        }
+                    }
+                    Ok(String::new())
+                }
+                ["get-property", bus_name, .., property] => {
+                    if let Some((left, ..)) = &mut state.pending {
+                        *left -= 1;
+                        if *left == 0 {
+                            let (_, pending_bus_name, method) = state.pending.take().unwrap();
+                            state.apply(&pending_bus_name, &method);
+                        }
+                    }
+                    let (_, status, title) = state
+                        .players
+                        .iter()
+                        .find(|(name, ..)| format!("{BUS_NAME_PREFIX}{name}") == *bus_name)
+                        .ok_or_else(|| format!("unknown bus name {bus_name}"))?;
+                    Ok(match *property {
+                        "PlaybackStatus" => format!("s \"{status}\"\n"),
+                        _ => format!("a{{sv}} 1 \"xesam:title\" s \"{title}\"\n"),
+                    })
+                }
+                _ => Err(format!("unexpected call {args:?}").into()),
+            }
+        }
+    }
+
+    fn with_players(players: &[(&str, &str, &str)], delay: usize) -> MprisBackend<FakeBus> {
+        let players = players
+            .iter()
+            .map(|(name, status, title)| (name.to_string(), status.to_string(), title.to_string()))
+            .collect();
+        MprisBackend::with_bus(FakeBus(Mutex::new(FakeState {
+            players,
+            delay,
+            pending: None,
+            calls: Vec::new(),
+        })))
+    }
+
+    /// Returns how many calls so far contain `pattern`.
+    fn calls(backend: &MprisBackend<FakeBus>, pattern: &str) -> usize {
+        let state = backend.bus.0.lock().unwrap();
+        state
+            .calls
+            .iter()
+            .filter(|call| call.contains(pattern))
+            .count()
+    }
+
+    fn title(track: &Track) -> &str {
+        track.title.as_deref().unwrap()
+    }
+
+    #[test]
+    fn prefers_the_playing_player() {
+        let mut backend = with_players(
+            &[
+                ("a", "Paused", "A"),
+                ("b", "Playing", "B"),
+                ("c", "Playing", "C"),
+            ],
+            0,
+        );
+        let track = backend.get_track().unwrap();
+        assert_eq!(
+            (title(&track), track.status),
+            ("B", PlaybackStatus::Playing)
+        );
+        assert_eq!(calls(&backend, "MediaPlayer2.c"), 0);
+    }
+
+    #[test]
+    fn falls_back_to_a_paused_player() {
+        let mut backend = with_players(&[("a", "Stopped", "A"), ("b", "Paused", "B")], 0);
+        assert_eq!(title(&backend.get_track().unwrap()), "B");
+        let mut backend = with_players(&[("a", "Stopped", "A"), ("b", "Stopped", "B")], 0);
+        assert_eq!(title(&backend.get_track().unwrap()), "A");
+    }
+
+    #[test]
+    fn reads_a_single_player_directly() {
+        let mut backend = with_players(&[("a", "Stopped", "A")], 0);
+        assert_eq!(title(&backend.get_track().unwrap()), "A");
+        // The status is only read along with the track.
+        assert_eq!(calls(&backend, "PlaybackStatus"), 1);
+        assert_eq!(calls(&backend, ""), 3);
+    }
+
+    #[test]
+    fn finds_the_selected_player() {
+        let mut backend = with_players(
+            &[
+                ("firefox.instance_1_42", "Paused", "F"),
+                ("spotify", "Playing", "S"),
+            ],
+            0,
+        );
+        backend.set_player(Some("firefox".to_string()));
+        assert_eq!(title(&backend.get_track().unwrap()), "F");
+        backend.set_player(Some("vlc".to_string()));
+        assert_eq!(
+            backend.get_track().unwrap_err().to_string(),
+            "player vlc not found (available: firefox.instance_1_42, spotify)"
+        );
+        let mut backend = with_players(&[], 0);
+        assert_eq!(
+            backend.get_track().unwrap_err().to_string(),
+            "no MPRIS player found"
+        );
+    }
+
+    #[test]
+    fn shows_the_player_that_was_commanded() {
+        let mut backend = with_players(&[("a", "Paused", "A"), ("b", "Playing", "B")], 0);
+        backend.play_pause().unwrap();
+        // Both are paused now, which would pick `a` again.
+        let track = backend.get_track().unwrap();
+        assert_eq!((title(&track), track.status), ("B", PlaybackStatus::Paused));
+        assert_eq!(title(&backend.get_track().unwrap()), "A");
+    }
+
+    #[test]
+    fn waits_for_the_command_to_apply() {
+        let mut backend = with_players(&[("a", "Playing", "A")], 3);
+        let start = Instant::now();
+        backend.play_pause().unwrap();
+        assert!(start.elapsed() < SETTLE_TIMEOUT);
+        // Once before the call, then until the third read shows the change.
+        assert_eq!(calls(&backend, "PlaybackStatus"), 4);
+        assert_eq!(backend.get_track().unwrap().status, PlaybackStatus::Paused);
+    }
+
+    #[test]
+    fn gives_up_on_commands_that_change_nothing() {
+        let mut backend = with_players(&[("a", "Playing", "A")], usize::MAX);
+        let start = Instant::now();
+        backend.next_track().unwrap();
+        assert!(start.elapsed() >= SETTLE_TIMEOUT);
+        let polls = SETTLE_TIMEOUT.as_millis() / SETTLE_POLL_INTERVAL.as_millis();
+        assert!(calls(&backend, "Metadata") as u128 <= polls + 2);
+        assert_eq!(calls(&backend, "PlaybackStatus"), 0);
+        assert_eq!(title(&backend.get_track().unwrap()), "A");
+    }
+}
@@ -0,0 +1,863 @@
+//! Minimal client for the PulseAudio native protocol.
+//!
+//! Only the handful of commands needed to query and change the volume and
+//! mute state of sinks and sources, and to switch the default sink, are
+//! implemented. PipeWire speaks the same
+//! protocol through `pipewire-pulse`, so this works on both.
+
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+use crate::{BackendError, BackendResult, MediaBackend, MediaController};
+
+const PROTOCOL_VERSION: u32 = 32;
+const COOKIE_LENGTH: usize = 256;
+const DESCRIPTOR_SIZE: usize = 20;
+const CONTROL_CHANNEL: u32 = u32::MAX;
+const INVALID_INDEX: u32 = u32::MAX;
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// `PA_VOLUME_NORM`, i.e. 100%.
+const VOLUME_NORM: u32 = 0x10000;
+
+const DEFAULT_SINK: &str = "@DEFAULT_SINK@";
+const DEFAULT_SOURCE: &str = "@DEFAULT_SOURCE@";
+
+const COMMAND_ERROR: u32 = 0;
+const COMMAND_REPLY: u32 = 2;
+const COMMAND_AUTH: u32 = 8;
+const COMMAND_SET_CLIENT_NAME: u32 = 9;
+const COMMAND_GET_SERVER_INFO: u32 = 20;
+const COMMAND_GET_SINK_INFO: u32 = 21;
+const COMMAND_GET_SINK_INFO_LIST: u32 = 22;
+const COMMAND_GET_SOURCE_INFO: u32 = 23;
+const COMMAND_SET_SINK_VOLUME: u32 = 36;
+const COMMAND_SET_SOURCE_VOLUME: u32 = 38;
+const COMMAND_SET_SINK_MUTE: u32 = 39;
+const COMMAND_SET_SOURCE_MUTE: u32 = 40;
+const COMMAND_SET_DEFAULT_SINK: u32 = 44;
+
+const TAG_STRING: u8 = b't';
+const TAG_STRING_NULL: u8 = b'N';
+const TAG_U32: u8 = b'L';
+const TAG_U8: u8 = b'B';
+const TAG_USEC: u8 = b'U';
+const TAG_VOLUME: u8 = b'V';
+const TAG_SAMPLE_SPEC: u8 = b'a';
+const TAG_ARBITRARY: u8 = b'x';
+const TAG_BOOLEAN_TRUE: u8 = b'1';
+const TAG_BOOLEAN_FALSE: u8 = b'0';
+const TAG_CHANNEL_MAP: u8 = b'm';
+const TAG_CVOLUME: u8 = b'v';
+const TAG_PROPLIST: u8 = b'P';
+const TAG_FORMAT_INFO: u8 = b'f';
+
+/// Serializes values in the tagged format used by every packet.
+#[derive(Default)]
+struct TagStruct(Vec<u8>);
+impl TagStruct {
+    fn put_u32(&mut self, value: u32) -> &mut Self {
+        self.0.push(TAG_U32);
+        self.0.extend(value.to_be_bytes());
+        self
+    }
+    fn put_string(&mut self, value: Option<&str>) -> &mut Self {
+        match value {
+            Some(value) => {
+                self.0.push(TAG_STRING);
+                self.0.extend(value.as_bytes());
+                self.0.push(0);
+            }
+            None => self.0.push(TAG_STRING_NULL),
+        }
+        self
+    }
+    fn put_bool(&mut self, value: bool) -> &mut Self {
+        self.0.push(if value {
+            TAG_BOOLEAN_TRUE
+        } else {
+            TAG_BOOLEAN_FALSE
+        });
+        self
+    }
+    fn put_arbitrary(&mut self, value: &[u8]) -> &mut Self {
+        self.0.push(TAG_ARBITRARY);
+        self.0.extend((value.len() as u32).to_be_bytes());
+        self.0.extend(value);
+        self
+    }
+    fn put_cvolume(&mut self, volumes: &[u32]) -> &mut Self {
+        self.0.push(TAG_CVOLUME);
+        self.0.push(volumes.len() as u8);
+        for volume in volumes {
+            self.0.extend(volume.to_be_bytes());
+        }
+        self
+    }
+    fn put_proplist(&mut self, properties: &[(&str, &str)]) -> &mut Self {
+        self.0.push(TAG_PROPLIST);
+        for (key, value) in properties {
+            let mut value = value.as_bytes().to_vec();
+            value.push(0);
+            self.put_string(Some(key))
+                .put_u32(value.len() as u32)
+                .put_arbitrary(&value);
+        }
+        self.put_string(None)
+    }
+}
+
+/// Reads values serialized by `TagStruct`.
+struct TagReader<'a> {
+    data: &'a [u8],
+}
+impl<'a> TagReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+    fn take(&mut self, count: usize) -> BackendResult<&'a [u8]> {
+        if self.data.len() < count {
+            return Err(BackendError::from("truncated PulseAudio packet"));
+        }
+        let (taken, rest) = self.data.split_at(count);
+        self.data = rest;
+        Ok(taken)
+    }
+    fn expect_tag(&mut self, tag: u8) -> BackendResult<()> {
+        let found = self.take(1)?[0];
+        if found != tag {
+            return Err(format!(
+                "unexpected tag {:?} in PulseAudio packet, expected {:?}",
+                found as char, tag as char
+            )
+            .into());
+        }
+        Ok(())
+    }
+    fn be_u32(&mut self) -> BackendResult<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+    fn get_u32(&mut self) -> BackendResult<u32> {
+        self.expect_tag(TAG_U32)?;
+        self.be_u32()
+    }
+    fn get_u8(&mut self) -> BackendResult<u8> {
+        self.expect_tag(TAG_U8)?;
+        Ok(self.take(1)?[0])
+    }
+    fn get_string(&mut self) -> BackendResult<Option<String>> {
+        match self.take(1)?[0] {
+            TAG_STRING_NULL => Ok(None),
+            TAG_STRING => {
+                let end = self
+                    .data
+                    .iter()
+                    .position(|b| *b == 0)
+                    .ok_or("unterminated string in PulseAudio packet")?;
+                let value = String::from_utf8_lossy(self.take(end)?).into_owned();
+                self.take(1)?;
+                Ok(Some(value))
+            }
+            tag => Err(format!("unexpected tag {:?}, expected a string", tag as char).into()),
+        }
+    }
+    fn get_bool(&mut self) -> BackendResult<bool> {
+        match self.take(1)?[0] {
+            TAG_BOOLEAN_TRUE => Ok(true),
+            TAG_BOOLEAN_FALSE => Ok(false),
+            tag => Err(format!("unexpected tag {:?}, expected a boolean", tag as char).into()),
+        }
+    }
+    fn skip_sample_spec(&mut self) -> BackendResult<()> {
+        self.expect_tag(TAG_SAMPLE_SPEC)?;
+        // Format (u8), channels (u8) and rate (u32).
+        self.take(6)?;
+        Ok(())
+    }
+    fn skip_channel_map(&mut self) -> BackendResult<()> {
+        self.expect_tag(TAG_CHANNEL_MAP)?;
+        let channels = self.take(1)?[0] as usize;
+        self.take(channels)?;
+        Ok(())
+    }
+    fn get_cvolume(&mut self) -> BackendResult<Vec<u32>> {
+        self.expect_tag(TAG_CVOLUME)?;
+        let channels = self.take(1)?[0] as usize;
+        (0..channels).map(|_| self.be_u32()).collect()
+    }
+    /// Skips the next value, whatever its type.
+    fn skip(&mut self) -> BackendResult<()> {
+        let tag = *self.data.first().ok_or("truncated PulseAudio packet")?;
+        match tag {
+            TAG_STRING | TAG_STRING_NULL => {
+                self.get_string()?;
+            }
+            TAG_BOOLEAN_TRUE | TAG_BOOLEAN_FALSE => {
+                self.take(1)?;
+            }
+            TAG_U8 => {
+                self.take(2)?;
+            }
+            TAG_U32 | TAG_VOLUME => {
+                self.take(5)?;
+            }
+            TAG_USEC => {
+                self.take(9)?;
+            }
+            TAG_SAMPLE_SPEC => self.skip_sample_spec()?,
+            TAG_CHANNEL_MAP => self.skip_channel_map()?,
+            TAG_CVOLUME => {
+                self.get_cvolume()?;
+            }
+            TAG_ARBITRARY => {
+                self.take(1)?;
+                let length = self.be_u32()?;
+                self.take(length as usize)?;
+            }
+            TAG_PROPLIST => {
+                self.take(1)?;
+                // Key, value length and value, until a null key.
+                while self.get_string()?.is_some() {
+                    self.get_u32()?;
+                    self.skip()?;
+                }
+            }
+            TAG_FORMAT_INFO => {
+                self.take(1)?;
+                // Encoding and properties.
+                self.skip()?;
+                self.skip()?;
+            }
+            _ => {
+                return Err(format!("unknown tag {:?} in PulseAudio packet", tag as char).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The part of a sink or source info reply that the backend cares about.
+struct DeviceInfo {
+    index: u32,
+    name: String,
+    description: Option<String>,
+    volumes: Vec<u32>,
+    mute: bool,
+}
+impl DeviceInfo {
+    fn parse(reply: &[u8]) -> BackendResult<Self> {
+        Self::read(&mut TagReader::new(reply))
+    }
+    /// Parses every entry of a sink or source info list reply.
+    fn parse_list(reply: &[u8]) -> BackendResult<Vec<Self>> {
+        let mut reader = TagReader::new(reply);
+        let mut devices = Vec::new();
+        while !reader.data.is_empty() {
+            devices.push(Self::read(&mut reader)?);
+        }
+        Ok(devices)
+    }
+    /// Reads a whole entry, so that the next one can follow in a list. Sink
+    /// and source entries have the same layout.
+    fn read(reader: &mut TagReader) -> BackendResult<Self> {
+        let index = reader.get_u32()?;
+        let name = reader.get_string()?.unwrap_or_default();
+        let description = reader.get_string()?;
+        reader.skip_sample_spec()?;
+        reader.skip_channel_map()?;
+        let _owner_module = reader.get_u32()?;
+        let volumes = reader.get_cvolume()?;
+        let mute = reader.get_bool()?;
+        // Monitor index and name, latency, driver, flags, properties,
+        // configured latency, base volume, state, volume steps and card.
+        for _ in 0..11 {
+            reader.skip()?;
+        }
+        // Name, description, priority and availability of every port, then
+        // the name of the active one.
+        let ports = reader.get_u32()?;
+        for _ in 0..ports as u64 * 4 + 1 {
+            reader.skip()?;
+        }
+        let formats = reader.get_u8()?;
+        for _ in 0..formats {
+            reader.skip()?;
+        }
+        Ok(Self {
+            index,
+            name,
+            description,
+            volumes,
+            mute,
+        })
+    }
+    fn percentage(&self) -> u8 {
+        if self.volumes.is_empty() {
+            return 0;
+        }
+        let average =
+            self.volumes.iter().map(|v| *v as u64).sum::<u64>() / self.volumes.len() as u64;
+        (average as f64 * 100.0 / VOLUME_NORM as f64)
+            .round()
+            .min(u8::MAX as f64) as u8
+    }
+}
+
+#[derive(Clone, Copy)]
+enum DeviceKind {
+    Sink,
+    Source,
+}
+impl DeviceKind {
+    fn get_info_command(&self) -> u32 {
+        match self {
+            Self::Sink => COMMAND_GET_SINK_INFO,
+            Self::Source => COMMAND_GET_SOURCE_INFO,
+        }
+    }
+    fn set_volume_command(&self) -> u32 {
+        match self {
+            Self::Sink => COMMAND_SET_SINK_VOLUME,
+            Self::Source => COMMAND_SET_SOURCE_VOLUME,
+        }
+    }
+    fn set_mute_command(&self) -> u32 {
+        match self {
+            Self::Sink => COMMAND_SET_SINK_MUTE,
+            Self::Source => COMMAND_SET_SOURCE_MUTE,
+        }
+    }
+}
+
+/// Talks to the sound server directly over its UNIX socket instead of
+/// spawning a process for every operation.
+///
+/// The connection is opened on first use and kept for the lifetime of the
+/// backend.
+pub struct PulseBackend {
+    socket_path: PathBuf,
+    stream: Option<UnixStream>,
+    next_tag: u32,
+    /// Names of the sink and source to control, `None` for the default ones.
+    sink: Option<String>,
+    source: Option<String>,
+    /// Highest sink volume in %.
+    max_volume: u8,
+}
+impl PulseBackend {
+    /// Uses the server pointed at by `$PULSE_SERVER`, or the default socket
+    /// under `$XDG_RUNTIME_DIR`.
+    pub fn new() -> Self {
+        Self::with_socket(Self::default_socket_path())
+    }
+    /// Uses the server listening on `socket_path`.
+    pub fn with_socket(socket_path: impl AsRef<Path>) -> Self {
+        Self {
+            socket_path: socket_path.as_ref().to_path_buf(),
+            stream: None,
+            next_tag: 0,
+            sink: None,
+            source: None,
+            max_volume: 100,
+        }
+    }
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+    fn default_socket_path() -> PathBuf {
+        if let Ok(server) = std::env::var("PULSE_SERVER") {
+            if let Some(path) = server.strip_prefix("unix:") {
+                return PathBuf::from(path);
+            }
+            if server.starts_with('/') {
+                return PathBuf::from(server);
+            }
+        }
+        let runtime_dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_string());
+        Path::new(&runtime_dir).join("pulse").join("native")
+    }
+    fn read_cookie() -> [u8; COOKIE_LENGTH] {
+        let mut cookie = [0; COOKIE_LENGTH];
+        let home = std::env::var("HOME").unwrap_or_default();
+        let config_home =
+            std::env::var("XDG_CONFIG_HOME").unwrap_or_else(|_| format!("{home}/.config"));
+        let candidates = [
+            std::env::var("PULSE_COOKIE").ok(),
+            Some(format!("{config_home}/pulse/cookie")),
+            Some(format!("{home}/.pulse-cookie")),
+        ];
+        // PipeWire doesn't check the cookie, so an empty one is fine there.
+        if let Some(data) = candidates
+            .into_iter()
+            .flatten()
+            .find_map(|path| std::fs::read(path).ok())
+        {
+            let len = data.len().min(COOKIE_LENGTH);
+            cookie[..len].copy_from_slice(&data[..len]);
+        }
+        cookie
+    }
+    fn connect(&mut self) -> BackendResult<()> {
+        let stream = UnixStream::connect(&self.socket_path).map_err(|err| {
+            format!(
+                "failed to connect to the sound server at {}: {err}",
+                self.socket_path.display()
+            )
+        })?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        self.stream = Some(stream);
+        self.request(COMMAND_AUTH, |t| {
+            t.put_u32(PROTOCOL_VERSION)
+                .put_arbitrary(&Self::read_cookie());
+        })?;
+        self.request(COMMAND_SET_CLIENT_NAME, |t| {
+            t.put_proplist(&[("application.name", env!("CARGO_PKG_NAME"))]);
+        })?;
+        Ok(())
+    }
+    fn write_packet(stream: &mut UnixStream, payload: &[u8]) -> std::io::Result<()> {
+        let mut packet = Vec::with_capacity(DESCRIPTOR_SIZE + payload.len());
+        for field in [payload.len() as u32, CONTROL_CHANNEL, 0, 0, 0] {
+            packet.extend(field.to_be_bytes());
+        }
+        packet.extend(payload);
+        stream.write_all(&packet)
+    }
+    fn read_packet(stream: &mut UnixStream) -> std::io::Result<(u32, Vec<u8>)> {
+        let mut descriptor = [0; DESCRIPTOR_SIZE];
+        stream.read_exact(&mut descriptor)?;
+        let length =
+            u32::from_be_bytes([descriptor[0], descriptor[1], descriptor[2], descriptor[3]]);
+        let channel =
+            u32::from_be_bytes([descriptor[4], descriptor[5], descriptor[6], descriptor[7]]);
+        let mut payload = vec![0; length as usize];
+        stream.read_exact(&mut payload)?;
+        Ok((channel, payload))
+    }
+    /// Sends a command and waits for its reply, returning the reply's
+    /// arguments.
+    fn request(
+        &mut self,
+        command: u32,
+        arguments: impl FnOnce(&mut TagStruct),
+    ) -> BackendResult<Vec<u8>> {
+        if self.stream.is_none() {
+            self.connect()?;
+        }
+        let tag = self.next_tag;
+        self.next_tag = self.next_tag.wrapping_add(1);
+
+        let mut tagstruct = TagStruct::default();
+        tagstruct.put_u32(command).put_u32(tag);
+        arguments(&mut tagstruct);
+
+        let result = self.exchange(tag, &tagstruct.0);
+        if matches!(result, Err(BackendError::Io(_))) {
+            // The connection is unusable after an I/O error, reconnect next time.
+            self.stream = None;
+        }
+        result
+    }
+    fn exchange(&mut self, tag: u32, payload: &[u8]) -> BackendResult<Vec<u8>> {
+        let stream = self.stream.as_mut().ok_or("not connected")?;
+        Self::write_packet(stream, payload)?;
+        loop {
+            let (channel, packet) = Self::read_packet(stream)?;
+            if channel != CONTROL_CHANNEL {
+                continue;
+            }
+            let mut reader = TagReader::new(&packet);
+            let command = reader.get_u32()?;
+            if reader.get_u32()? != tag {
+                // Events and replies meant for something else.
+                continue;
+            }
+            return match command {
+                COMMAND_REPLY => Ok(reader.data.to_vec()),
+                COMMAND_ERROR => {
+                    Err(format!("sound server returned error code {}", reader.get_u32()?).into())
+                }
+                _ => Err(format!("unexpected PulseAudio command {command}").into()),
+            };
+        }
+    }
+    fn device_name(&self, kind: DeviceKind) -> String {
+        match kind {
+            DeviceKind::Sink => self.sink.as_deref().unwrap_or(DEFAULT_SINK),
+            DeviceKind::Source => self.source.as_deref().unwrap_or(DEFAULT_SOURCE),
+        }
+        .to_string()
+    }
+    fn get_info(&mut self, kind: DeviceKind) -> BackendResult<DeviceInfo> {
+        let name = self.device_name(kind);
+        let reply = self
+            .request(kind.get_info_command(), |t| {
+                t.put_u32(INVALID_INDEX).put_string(Some(&name));
+            })
+            .map_err(|err| format!("{name}: {err}"))?;
+        DeviceInfo::parse(&reply)
+    }
+    fn set_mute(&mut self, kind: DeviceKind, mute: bool) -> BackendResult<()> {
+        let name = self.device_name(kind);
+        self.request(kind.set_mute_command(), |t| {
+            t.put_u32(INVALID_INDEX)
+                .put_string(Some(&name))
+                .put_bool(mute);
+        })?;
+        Ok(())
+    }
+    fn set_volumes(&mut self, kind: DeviceKind, volumes: &[u32]) -> BackendResult<()> {
+        let name = self.device_name(kind);
+        self.request(kind.set_volume_command(), |t| {
+            t.put_u32(INVALID_INDEX)
+                .put_string(Some(&name))
+                .put_cvolume(volumes);
+        })?;
+        Ok(())
+    }
+    /// Only sinks can be boosted.
+    fn max_volume_of(&self, kind: DeviceKind) -> u8 {
+        match kind {
+            DeviceKind::Sink => self.max_volume.max(100),
+            DeviceKind::Source => 100,
+        }
+    }
+    fn get_description(&mut self, kind: DeviceKind) -> BackendResult<String> {
+        let info = self.get_info(kind)?;
+        Ok(info.description.unwrap_or(info.name))
+    }
+    fn default_sink_name(&mut self) -> BackendResult<Option<String>> {
+        let reply = self.request(COMMAND_GET_SERVER_INFO, |_| {})?;
+        let mut reader = TagReader::new(&reply);
+        // Package name and version, user name and host name.
+        for _ in 0..4 {
+            reader.skip()?;
+        }
+        reader.skip_sample_spec()?;
+        reader.get_string()
+    }
+    fn toggle_mute(&mut self, kind: DeviceKind) -> BackendResult<()> {
+        let mute = self.get_info(kind)?.mute;
+        self.set_mute(kind, !mute)
+    }
+    fn inc_volume_of(&mut self, kind: DeviceKind, inc: f32) -> BackendResult<()> {
+        let info = self.get_info(kind)?;
+        if info.mute {
+            self.set_mute(kind, false)?;
+        }
+        let delta = (inc.abs() * VOLUME_NORM as f32 / 100.0).round() as u32;
+        let limit = self.max_volume_of(kind) as u32 * VOLUME_NORM / 100;
+        let volumes = info
+            .volumes
+            .iter()
+            .map(|volume| {
+                if inc < 0.0 {
+                    volume.saturating_sub(delta)
+                } else {
+                    // Never push a channel past the limit, but leave it alone if it already is.
+                    (*volume).max((volume + delta).min(limit))
+                }
+            })
+            .collect::<Vec<_>>();
+        self.set_volumes(kind, &volumes)
+    }
+    fn set_volume_of(&mut self, kind: DeviceKind, volume: f32) -> BackendResult<()> {
+        let info = self.get_info(kind)?;
+        if info.mute {
+            self.set_mute(kind, false)?;
+        }
+        // Like `pactl set-sink-volume`, every channel gets the same volume.
+        let volume = volume.clamp(0.0, self.max_volume_of(kind) as f32);
+        let volume = (volume * VOLUME_NORM as f32 / 100.0).round() as u32;
+        self.set_volumes(kind, &vec![volume; info.volumes.len()])
+    }
+}
+impl std::default::Default for PulseBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl MediaBackend for PulseBackend {
+    fn configure(&mut self, controller: &MediaController) -> BackendResult<()> {
+        self.sink = controller.sink().map(str::to_string);
+        self.source = controller.source().map(str::to_string);
+        self.max_volume = controller.max_volume;
+        Ok(())
+    }
+    fn get_volume_mute(&mut self) -> BackendResult<bool> {
+        Ok(self.get_info(DeviceKind::Sink)?.mute)
+    }
+    fn get_microphone_mute(&mut self) -> BackendResult<bool> {
+        Ok(self.get_info(DeviceKind::Source)?.mute)
+    }
+    fn get_volume(&mut self) -> BackendResult<u8> {
+        Ok(self.get_info(DeviceKind::Sink)?.percentage())
+    }
+    fn get_microphone_volume(&mut self) -> BackendResult<u8> {
+        Ok(self.get_info(DeviceKind::Source)?.percentage())
+    }
+    fn inc_volume(&mut self, inc: f32) -> BackendResult<()> {
+        self.inc_volume_of(DeviceKind::Sink, inc)
+    }
+    fn inc_microphone_volume(&mut self, inc: f32) -> BackendResult<()> {
+        self.inc_volume_of(DeviceKind::Source, inc)
+    }
+    fn set_volume(&mut self, volume: f32) -> BackendResult<()> {
+        self.set_volume_of(DeviceKind::Sink, volume)
+    }
+    fn set_microphone_volume(&mut self, volume: f32) -> BackendResult<()> {
+        self.set_volume_of(DeviceKind::Source, volume)
+    }
+    fn toggle_volume_mute(&mut self) -> BackendResult<()> {
+        self.toggle_mute(DeviceKind::Sink)
+    }
+    fn toggle_microphone_mute(&mut self) -> BackendResult<()> {
+        self.toggle_mute(DeviceKind::Source)
+    }
+    fn set_volume_mute(&mut self, mute: bool) -> BackendResult<()> {
+        self.set_mute(DeviceKind::Sink, mute)
+    }
+    fn set_microphone_mute(&mut self, mute: bool) -> BackendResult<()> {
+        self.set_mute(DeviceKind::Source, mute)
+    }
+    fn get_volume_device(&mut self) -> BackendResult<String> {
+        self.get_description(DeviceKind::Sink)
+    }
+    fn get_microphone_device(&mut self) -> BackendResult<String> {
+        self.get_description(DeviceKind::Source)
+    }
+    fn next_volume_device(&mut self) -> BackendResult<()> {
+        let default = self.default_sink_name()?;
+        let reply = self.request(COMMAND_GET_SINK_INFO_LIST, |_| {})?;
+        let mut sinks = DeviceInfo::parse_list(&reply)?;
+        sinks.sort_by_key(|sink| sink.index);
+        let next = match sinks
+            .iter()
+            .position(|sink| Some(&sink.name) == default.as_ref())
+        {
+            Some(i) => (i + 1) % sinks.len(),
+            None => 0,
+        };
+        let name = sinks.get(next).ok_or("no sinks found")?.name.clone();
+        self.request(COMMAND_SET_DEFAULT_SINK, |t| {
+            t.put_string(Some(&name));
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// `PA_ERR_NOENTITY`.
+    const ERROR_NO_ENTITY: u32 = 5;
+
+    /// Appends a sink info entry, with every field the backend skips.
+    fn put_sink_info(t: &mut TagStruct, index: u32, name: &str, volumes: &[u32], mute: bool) {
+        t.put_u32(index)
+            .put_string(Some(name))
+            .put_string(Some("Built-in Audio"));
+        // Sample spec: format, channels and rate.
+        t.0.extend([TAG_SAMPLE_SPEC, 3, volumes.len() as u8]);
+        t.0.extend(44100u32.to_be_bytes());
+        t.0.extend([TAG_CHANNEL_MAP, volumes.len() as u8]);
+        t.0.extend((1..=volumes.len() as u8).collect::<Vec<_>>());
+        t.put_u32(7).put_cvolume(volumes).put_bool(mute);
+        // Monitor index and name, latency, driver, flags, properties,
+        // configured latency, base volume, state, volume steps and card.
+        t.put_u32(index + 1).put_string(Some("monitor"));
+        t.0.push(TAG_USEC);
+        t.0.extend(1000u64.to_be_bytes());
+        t.put_string(None).put_u32(0);
+        t.put_proplist(&[("device.class", "sound")]);
+        t.0.push(TAG_USEC);
+        t.0.extend(0u64.to_be_bytes());
+        t.0.push(TAG_VOLUME);
+        t.0.extend(VOLUME_NORM.to_be_bytes());
+        t.put_u32(0).put_u32(VOLUME_NORM + 1).put_u32(2);
+        // Two ports and the active one.
+        t.put_u32(2);
+        for port in ["analog-output-speaker", "analog-output-headphones"] {
+            t.put_string(Some(port))
+                .put_string(Some("Port"))
+                .put_u32(100)
+                .put_u32(0);
+        }
+        t.put_string(Some("analog-output-speaker"));
+        // Two formats, each an encoding and properties.
+        t.0.extend([TAG_U8, 2]);
+        for encoding in [1, 2] {
+            t.0.extend([TAG_FORMAT_INFO, TAG_U8, encoding]);
+            t.put_proplist(&[("format.rate", "44100")]);
+        }
+    }
+
+    /// A sound server answering just enough of the protocol, with a single
+    /// sink named `sink`.
+    struct MockServer {
+        socket_path: PathBuf,
+        volumes: Arc<Mutex<Vec<u32>>>,
+        connections: Arc<AtomicUsize>,
+        /// Closes the connection instead of answering the next request.
+        hang_up: Arc<AtomicBool>,
+    }
+    impl MockServer {
+        fn start(name: &str, volumes: &[u32]) -> Self {
+            let socket_path = std::env::temp_dir().join(format!(
+                "media-controller-test-{}-{name}.sock",
+                std::process::id()
+            ));
+            let _ = std::fs::remove_file(&socket_path);
+            let listener = UnixListener::bind(&socket_path).unwrap();
+            let server = Self {
+                socket_path,
+                volumes: Arc::new(Mutex::new(volumes.to_vec())),
+                connections: Arc::default(),
+                hang_up: Arc::default(),
+            };
+            let volumes = server.volumes.clone();
+            let connections = server.connections.clone();
+            let hang_up = server.hang_up.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    connections.fetch_add(1, Ordering::SeqCst);
+                    Self::serve(stream, &volumes, &hang_up);
+                }
+            });
+            server
+        }
+        fn serve(mut stream: UnixStream, volumes: &Mutex<Vec<u32>>, hang_up: &AtomicBool) {
+            while let Ok((_, packet)) = PulseBackend::read_packet(&mut stream) {
+                if hang_up.swap(false, Ordering::SeqCst) {
+                    return;
+                }
+                let mut reader = TagReader::new(&packet);
+                let command = reader.get_u32().unwrap();
+                let tag = reader.get_u32().unwrap();
+                let mut reply = TagStruct::default();
+                reply.put_u32(COMMAND_REPLY).put_u32(tag);
+                match command {
+                    COMMAND_AUTH => {
+                        reply.put_u32(PROTOCOL_VERSION);
+                    }
+                    COMMAND_SET_CLIENT_NAME => {
+                        reply.put_u32(1);
+                    }
+                    COMMAND_GET_SINK_INFO => {
+                        reader.get_u32().unwrap();
+                        if reader.get_string().unwrap().as_deref() == Some("sink") {
+                            put_sink_info(&mut reply, 0, "sink", &volumes.lock().unwrap(), false);
+                        } else {
+                            reply = TagStruct::default();
+                            reply
+                                .put_u32(COMMAND_ERROR)
+                                .put_u32(tag)
+                                .put_u32(ERROR_NO_ENTITY);
+                        }
+                    }
+                    COMMAND_SET_SINK_VOLUME => {
+                        reader.get_u32().unwrap();
+                        reader.get_string().unwrap();
+                        *volumes.lock().unwrap() = reader.get_cvolume().unwrap();
+                    }
+                    _ => panic!("unexpected command {command}"),
+                }
+                PulseBackend::write_packet(&mut stream, &reply.0).unwrap();
+            }
+        }
+        fn backend(&self) -> PulseBackend {
+            let mut backend = PulseBackend::with_socket(&self.socket_path);
+            backend.sink = Some("sink".to_string());
+            backend
+        }
+    }
+    impl Drop for MockServer {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.socket_path);
+        }
+    }
+
+    #[test]
+    fn device_info_skips_to_the_next_entry() {
+        let mut list = TagStruct::default();
+        put_sink_info(
+            &mut list,
+            0,
+            "speakers",
+            &[VOLUME_NORM, VOLUME_NORM / 2],
+            true,
+        );
+        put_sink_info(&mut list, 2, "headphones", &[VOLUME_NORM / 4], false);
+        let devices = DeviceInfo::parse_list(&list.0).unwrap();
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].name, "speakers");
+        assert_eq!(devices[0].description.as_deref(), Some("Built-in Audio"));
+        assert!(devices[0].mute);
+        assert_eq!(devices[0].percentage(), 75);
+        assert_eq!(devices[1].index, 2);
+        assert_eq!(devices[1].name, "headphones");
+        assert!(!devices[1].mute);
+        assert_eq!(devices[1].percentage(), 25);
+    }
+
+    #[test]
+    fn reads_the_volume() {
+        let server = MockServer::start("read", &[VOLUME_NORM * 2 / 5; 2]);
+        let mut backend = server.backend();
+        assert_eq!(backend.get_volume().unwrap(), 40);
+        assert_eq!(backend.get_volume_device().unwrap(), "Built-in Audio");
+        assert!(!backend.get_volume_mute().unwrap());
+    }
+
+    #[test]
+    fn inc_volume_stops_at_max_volume() {
+        let server = MockServer::start("inc", &[VOLUME_NORM * 95 / 100, VOLUME_NORM * 110 / 100]);
+        let mut backend = server.backend();
+        backend.inc_volume(10.0).unwrap();
+        // The first channel is clamped, the second one was already past the
+        // limit and is left alone.
+        assert_eq!(
+            *server.volumes.lock().unwrap(),
+            [VOLUME_NORM, VOLUME_NORM * 110 / 100]
+        );
+
+        backend.max_volume = 150;
+        backend.inc_volume(50.0).unwrap();
+        assert_eq!(*server.volumes.lock().unwrap(), [VOLUME_NORM * 3 / 2; 2]);
+
+        backend.inc_volume(-200.0).unwrap();
+        assert_eq!(*server.volumes.lock().unwrap(), [0, 0]);
+    }
+
+    #[test]
+    fn reports_error_replies() {
+        let server = MockServer::start("error", &[VOLUME_NORM]);
+        let mut backend = server.backend();
+        backend.sink = Some("missing".to_string());
+        let err = backend.get_volume().unwrap_err().to_string();
+        assert_eq!(err, "missing: sound server returned error code 5");
+        // The connection is still usable.
+        backend.sink = Some("sink".to_string());
+        assert_eq!(backend.get_volume().unwrap(), 100);
+        assert_eq!(server.connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn reconnects_after_an_io_error() {
+        let server = MockServer::start("reconnect", &[VOLUME_NORM / 2]);
+        let mut backend = server.backend();
+        assert_eq!(backend.get_volume().unwrap(), 50);
+        server.hang_up.store(true, Ordering::SeqCst);
+        assert!(backend.get_volume().is_err());
+        assert!(backend.stream.is_none());
+        assert_eq!(backend.get_volume().unwrap(), 50);
+        assert_eq!(server.connections.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,220 @@
+//! Location of the lock files and sockets used to talk to other instances.
+
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use crate::cli::NAME;
+
+/// Directory holding the lock files and sockets of the current user.
+///
+/// It is `$XDG_RUNTIME_DIR/media-controller`, or `/tmp/media-controller-$UID`
+/// when `$XDG_RUNTIME_DIR` isn't set. The latter is created with mode 0700 and
+/// refused if it is owned by someone else or accessible by other users.
+pub struct RuntimeDir {
+    dir: PathBuf,
+    /// Prefix of every file name, e.g. `media-controller-seat0`.
+    prefix: String,
+}
+impl RuntimeDir {
+    /// `namespace` separates the instances of the same user, so that each
+    /// seat or display gets its own window. `seat` and `display` are resolved
+    /// from the environment, any other value is used as it is.
+    pub fn new(namespace: Option<&str>) -> Result<Self, String> {
+        let dir = match std::env::var_os("XDG_RUNTIME_DIR") {
+            Some(dir) if !dir.is_empty() => Path::new(&dir).join(NAME),
+            _ => std::env::temp_dir().join(format!("{NAME}-{}", current_uid())),
+        };
+        Self::ensure_private(&dir)?;
+        let prefix = match namespace.map(resolve_namespace).transpose()? {
+            Some(namespace) => format!("{NAME}-{namespace}"),
+            None => NAME.to_string(),
+        };
+        Ok(Self { dir, prefix })
+    }
+    fn ensure_private(dir: &Path) -> Result<(), String> {
+        let failed = |err: std::io::Error| format!("{}: {err}", dir.display());
+        if let Err(err) = std::fs::DirBuilder::new().mode(0o700).create(dir) {
+            if err.kind() != std::io::ErrorKind::AlreadyExists {
+                return Err(failed(err));
+            }
+        }
+        // Not following symlinks, so that another user can't point it
+        // somewhere else.
+        let metadata = std::fs::symlink_metadata(dir).map_err(failed)?;
+        if !metadata.is_dir() {
+            return Err(format!("{} is not a directory", dir.display()));
+        }
+        if metadata.uid() != current_uid() {
+            return Err(format!("{} is owned by another user", dir.display()));
+        }
+        if metadata.permissions().mode() & 0o077 != 0 {
+            return Err(format!(
+                "{} is accessible by other users, expected mode 0700",
+                dir.display()
+            ));
+        }
+        Ok(())
+    }
+    fn path(&self, suffix: &str) -> PathBuf {
+        self.dir.join(format!("{}{suffix}", self.prefix))
+    }
+    /// Held by the instance that owns the window.
+    pub fn lock(&self) -> PathBuf {
+        self.path(".lock")
+    }
+    /// Where the instance that owns the window listens for updates.
+    pub fn socket(&self) -> PathBuf {
+        self.path(".sock")
+    }
+    /// Held by the daemon.
+    pub fn daemon_lock(&self) -> PathBuf {
+        self.path("-daemon.lock")
+    }
+    /// Where the daemon listens for commands.
+    pub fn daemon_socket(&self) -> PathBuf {
+        self.path("-daemon.sock")
+    }
+}
+
+fn current_uid() -> u32 {
+    // SAFETY: `geteuid` has no preconditions and always succeeds.
+    unsafe { libc::geteuid() }
+}
+
+fn resolve_namespace(namespace: &str) -> Result<String, String> {
+    let resolved = match namespace {
+        "seat" => std::env::var("XDG_SEAT").unwrap_or_else(|_| "seat0".to_string()),
+        "display" => std::env::var("WAYLAND_DISPLAY")
+            .or_else(|_| std::env::var("DISPLAY"))
+            .map_err(|_| "namespace display requires $WAYLAND_DISPLAY or $DISPLAY")?,
+        _ => namespace.to_string(),
+    };
+    // Displays look like `:0` or `wayland-1`, keep file names tidy.
+    Ok(resolved
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scratch directory, removed when dropped.
+    struct TempDir {
+        path: PathBuf,
+    }
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "media-controller-test-{}-{name}",
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            Self { path }
+        }
+    }
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+
+    fn mode(path: &Path) -> u32 {
+        std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn creates_a_private_directory() {
+        let temp = TempDir::new("runtime-create");
+        let dir = temp.path.join(NAME);
+        RuntimeDir::ensure_private(&dir).unwrap();
+        assert_eq!(mode(&dir), 0o700);
+        // Existing ones are kept.
+        RuntimeDir::ensure_private(&dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_shared_directories() {
+        let temp = TempDir::new("runtime-shared");
+        let dir = temp.path.join(NAME);
+        std::fs::DirBuilder::new().mode(0o755).create(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(
+            RuntimeDir::ensure_private(&dir),
+            Err(format!(
+                "{} is accessible by other users, expected mode 0700",
+                dir.display()
+            ))
+        );
+    }
+
+    #[test]
+    fn refuses_files_and_symlinks() {
+        let temp = TempDir::new("runtime-links");
+        let file = temp.path.join("file");
+        std::fs::write(&file, "").unwrap();
+        let link = temp.path.join("link");
+        let target = temp.path.join("target");
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&target)
+            .unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        for path in [file, link] {
+            assert_eq!(
+                RuntimeDir::ensure_private(&path),
+                Err(format!("{} is not a directory", path.display()))
+            );
+        }
+    }
+
+    #[test]
+    fn refuses_directories_of_other_users() {
+        let temp = TempDir::new("runtime-owner");
+        let dir = temp.path.join(NAME);
+        std::fs::DirBuilder::new().mode(0o700).create(&dir).unwrap();
+        let other = if current_uid() == 0 { 65534 } else { 0 };
+        if std::os::unix::fs::chown(&dir, Some(other), None).is_err() {
+            // Only root can give the directory away, anyone else checks
+            // against one owned by root.
+            assert_eq!(std::fs::metadata("/").unwrap().uid(), 0);
+            let root = Path::new("/");
+            assert_eq!(
+                RuntimeDir::ensure_private(root),
+                Err("/ is owned by another user".to_string())
+            );
+            return;
+        }
+        assert_eq!(
+            RuntimeDir::ensure_private(&dir),
+            Err(format!("{} is owned by another user", dir.display()))
+        );
+    }
+
+    #[test]
+    fn resolves_namespaces() {
+        // Every variable is set and checked here, as tests run in parallel.
+        std::env::set_var("XDG_SEAT", "seat1");
+        assert_eq!(resolve_namespace("seat"), Ok("seat1".to_string()));
+        std::env::remove_var("XDG_SEAT");
+        assert_eq!(resolve_namespace("seat"), Ok("seat0".to_string()));
+
+        std::env::set_var("WAYLAND_DISPLAY", "wayland-1");
+        std::env::set_var("DISPLAY", ":0");
+        assert_eq!(resolve_namespace("display"), Ok("wayland-1".to_string()));
+        std::env::remove_var("WAYLAND_DISPLAY");
+        assert_eq!(resolve_namespace("display"), Ok("_0".to_string()));
+        std::env::remove_var("DISPLAY");
+        assert_eq!(
+            resolve_namespace("display"),
+            Err("namespace display requires $WAYLAND_DISPLAY or $DISPLAY".to_string())
+        );
+
+        assert_eq!(resolve_namespace("my desk/2"), Ok("my_desk_2".to_string()));
+    }
+}
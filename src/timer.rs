@@ -0,0 +1,195 @@
+//! When to hide the window: a deadline pushed back by every update.
+//!
+//! The time comes from a `Clock`, so that the timer can be driven by hand
+//! instead of waiting for it.
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Source of the current time.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// The actual time.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Where the window is in its lifespan.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    /// Fully shown, with the time left before fading out.
+    Shown(Duration),
+    /// Fading out, with the time left before hiding.
+    Fading(Duration),
+    /// The window has to be hidden.
+    Expired,
+    /// Not counting, e.g. while the window is hidden.
+    Stopped,
+}
+
+/// Counts down from the last update of the window: it stays shown for
+/// `duration`, then fades out for `fade_out`.
+#[derive(Debug)]
+pub struct HideTimer<C = SystemClock> {
+    clock: C,
+    duration: Duration,
+    fade_out: Duration,
+    /// When the window was last updated, `None` while stopped.
+    updated: Option<Instant>,
+}
+impl HideTimer {
+    /// Starts counting right away.
+    pub fn new(duration: Duration, fade_out: Duration) -> Self {
+        Self::with_clock(SystemClock, duration, fade_out)
+    }
+}
+impl<C: Clock> HideTimer<C> {
+    /// Starts counting right away, with the time given by `clock`.
+    pub fn with_clock(clock: C, duration: Duration, fade_out: Duration) -> Self {
+        let updated = Some(clock.now());
+        Self {
+            clock,
+            duration,
+            fade_out,
+            updated,
+        }
+    }
+    /// Counts again from now.
+    pub fn restart(&mut self) {
+        self.updated = Some(self.clock.now());
+    }
+    pub fn stop(&mut self) {
+        self.updated = None;
+    }
+    pub fn phase(&self) -> Phase {
+        let Some(updated) = self.updated else {
+            return Phase::Stopped;
+        };
+        let elapsed = self.clock.now().saturating_duration_since(updated);
+        let lifespan = self.duration + self.fade_out;
+        if elapsed < self.duration {
+            Phase::Shown(self.duration - elapsed)
+        } else if elapsed < lifespan {
+            Phase::Fading(lifespan - elapsed)
+        } else {
+            Phase::Expired
+        }
+    }
+}
+
+/// A `HideTimer` shared between the thread waiting for it and the ones
+/// restarting it.
+#[derive(Clone)]
+pub struct SharedHideTimer {
+    inner: Arc<(Mutex<HideTimer>, Condvar)>,
+}
+impl SharedHideTimer {
+    pub fn new(timer: HideTimer) -> Self {
+        Self {
+            inner: Arc::new((Mutex::new(timer), Condvar::new())),
+        }
+    }
+    pub fn lock(&self) -> MutexGuard<'_, HideTimer> {
+        self.inner.0.lock().unwrap()
+    }
+    /// Restarts the timer and wakes up whoever is waiting for it.
+    pub fn restart(&self) {
+        self.lock().restart();
+        self.inner.1.notify_all();
+    }
+    /// Releases the timer until it is restarted or `timeout` runs out.
+    /// `None` waits for a restart.
+    pub fn wait<'a>(
+        &self,
+        timer: MutexGuard<'a, HideTimer>,
+        timeout: Option<Duration>,
+    ) -> MutexGuard<'a, HideTimer> {
+        let changed = &self.inner.1;
+        match timeout {
+            Some(timeout) => changed.wait_timeout(timer, timeout).unwrap().0,
+            None => changed.wait(timer).unwrap(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    const DURATION: Duration = Duration::from_millis(1000);
+    const FADE_OUT: Duration = Duration::from_millis(200);
+
+    /// A clock that only moves when told to.
+    #[derive(Clone)]
+    struct ManualClock(Rc<Cell<Instant>>);
+    impl ManualClock {
+        fn new() -> Self {
+            Self(Rc::new(Cell::new(Instant::now())))
+        }
+        fn advance(&self, millis: u64) {
+            self.0.set(self.0.get() + Duration::from_millis(millis));
+        }
+    }
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            self.0.get()
+        }
+    }
+
+    fn millis(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn goes_through_every_phase() {
+        let clock = ManualClock::new();
+        let timer = HideTimer::with_clock(clock.clone(), DURATION, FADE_OUT);
+        assert_eq!(timer.phase(), Phase::Shown(millis(1000)));
+        clock.advance(999);
+        assert_eq!(timer.phase(), Phase::Shown(millis(1)));
+        clock.advance(1);
+        assert_eq!(timer.phase(), Phase::Fading(millis(200)));
+        clock.advance(150);
+        assert_eq!(timer.phase(), Phase::Fading(millis(50)));
+        clock.advance(50);
+        assert_eq!(timer.phase(), Phase::Expired);
+        clock.advance(10_000);
+        assert_eq!(timer.phase(), Phase::Expired);
+    }
+
+    #[test]
+    fn restart_counts_from_now() {
+        let clock = ManualClock::new();
+        let mut timer = HideTimer::with_clock(clock.clone(), DURATION, FADE_OUT);
+        clock.advance(1100);
+        assert_eq!(timer.phase(), Phase::Fading(millis(100)));
+        timer.restart();
+        assert_eq!(timer.phase(), Phase::Shown(millis(1000)));
+        clock.advance(400);
+        assert_eq!(timer.phase(), Phase::Shown(millis(600)));
+        clock.advance(800);
+        assert_eq!(timer.phase(), Phase::Expired);
+    }
+
+    #[test]
+    fn stop_holds_until_restarted() {
+        let clock = ManualClock::new();
+        let mut timer = HideTimer::with_clock(clock.clone(), DURATION, FADE_OUT);
+        clock.advance(500);
+        timer.stop();
+        assert_eq!(timer.phase(), Phase::Stopped);
+        clock.advance(10_000);
+        assert_eq!(timer.phase(), Phase::Stopped);
+        timer.restart();
+        assert_eq!(timer.phase(), Phase::Shown(millis(1000)));
+    }
+}
@@ -1,8 +1,35 @@
-use gtk::glib::{ControlFlow, Propagation};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Instant;
+
+use gtk::glib::{ControlFlow, MainContext, Propagation};
 use gtk::prelude::*;
 use gtk::{Application, ApplicationWindow};
 
-use crate::{Color, MediaController};
+use crate::animation::{BarAnimation, Reveal};
+use crate::cli::NAME;
+use crate::{Anchor, Color, MediaController, Monitor, SharedOsdState, Side};
+
+/// Height of the drawn progress bar relative to the window.
+const BAR_HEIGHT_RATIO: f64 = 0.5;
+const BAR_BORDER_WIDTH: f64 = 1.0;
+/// Space around the drawn progress bar in px.
+const BAR_SPACING: i32 = 8;
+const ICON_SIZE: i32 = 16;
+
+thread_local! {
+    /// Applies the shared state to the window. Only set on the GTK thread.
+    static APPLY_STATE: RefCell<Option<Box<dyn FnMut()>>> = const { RefCell::new(None) };
+}
+
+/// Runs on the GTK thread after another thread changed the shared state.
+fn apply_state() {
+    APPLY_STATE.with(|apply| {
+        if let Some(apply) = apply.borrow_mut().as_mut() {
+            apply();
+        }
+    });
+}
 
 fn set_visual(window: &ApplicationWindow, _screen: Option<&gtk::gdk::Screen>) {
     if let Some(screen) = GtkWindowExt::screen(window) {
@@ -12,33 +39,260 @@ fn set_visual(window: &ApplicationWindow, _screen: Option<&gtk::gdk::Screen>) {
     }
 }
 
-fn draw(_win: &ApplicationWindow, ctx: &gtk::cairo::Context, bg: Color) -> Propagation {
+fn draw(
+    _win: &ApplicationWindow,
+    ctx: &gtk::cairo::Context,
+    shared: &SharedOsdState,
+) -> Propagation {
+    let bg = shared.lock().color;
     ctx.set_source_rgba(bg.r as f64, bg.g as f64, bg.b as f64, bg.a as f64);
     ctx.paint().unwrap();
     Propagation::Proceed
 }
 
-fn realize(win: &ApplicationWindow, width: i32, bottom: i32) {
+fn set_source(ctx: &gtk::cairo::Context, color: Color) {
+    ctx.set_source_rgba(
+        color.r as f64,
+        color.g as f64,
+        color.b as f64,
+        color.a as f64,
+    );
+}
+
+fn draw_bar(
+    area: &gtk::DrawingArea,
+    ctx: &gtk::cairo::Context,
+    shared: &SharedOsdState,
+    animation: &RefCell<BarAnimation>,
+) -> Propagation {
+    let (maximum, colors) = {
+        let shared = shared.lock();
+        (shared.bar_maximum, shared.bar_colors)
+    };
+    let Some(level) = animation.borrow().level(Instant::now()) else {
+        return Propagation::Proceed;
+    };
+    let width = area.allocated_width() as f64;
+    let height = area.allocated_height() as f64 * BAR_HEIGHT_RATIO;
+    let y = (area.allocated_height() as f64 - height) / 2.0;
+    let maximum = maximum.max(1) as f64;
+    let level = level.min(maximum);
+    let step = width / maximum;
+
+    set_source(ctx, colors.track);
+    ctx.rectangle(0.0, y, width, height);
+    ctx.fill().unwrap();
+
+    set_source(ctx, colors.fill);
+    ctx.rectangle(0.0, y, step * level.min(100.0), height);
+    ctx.fill().unwrap();
+
+    if level > 100.0 {
+        set_source(ctx, colors.over);
+        ctx.rectangle(step * 100.0, y, step * (level - 100.0), height);
+        ctx.fill().unwrap();
+    }
+
+    // Stroked inside the allocation so that it isn't clipped.
+    let inset = BAR_BORDER_WIDTH / 2.0;
+    set_source(ctx, colors.border);
+    ctx.set_line_width(BAR_BORDER_WIDTH);
+    ctx.rectangle(
+        inset,
+        y + inset,
+        width - BAR_BORDER_WIDTH,
+        height - BAR_BORDER_WIDTH,
+    );
+    ctx.stroke().unwrap();
+    Propagation::Proceed
+}
+
+/// Shows the progress bar at the level it has reached. Returns whether it is
+/// still moving.
+fn step_animation(
+    win: &ApplicationWindow,
+    label: &gtk::Label,
+    shared: &SharedOsdState,
+    animation: &RefCell<BarAnimation>,
+) -> bool {
+    let now = Instant::now();
+    let animation = animation.borrow();
+    let level = animation.level(now).map(|level| level.round() as u8);
+    let text = shared.lock().text(level);
+    if label.text().as_str() != text {
+        label.set_text(&text);
+    }
+    win.queue_draw();
+    animation.is_running(now)
+}
+
+/// Shows the window as far as it has appeared, either through its opacity or
+/// by moving it from `position` towards the side it slides out of, given
+/// with the distance to cover. Returns whether it is still moving.
+fn step_reveal(
+    win: &ApplicationWindow,
+    shared: &SharedOsdState,
+    reveal: &RefCell<Reveal>,
+    position: Option<(i32, i32)>,
+    slide: Option<(Side, i32)>,
+) -> bool {
+    let now = Instant::now();
+    let reveal = reveal.borrow();
+    let frame = {
+        let shared = shared.lock();
+        let distance = slide.map(|(_, distance)| distance);
+        reveal.frame(now, shared.show_animation, shared.hide_animation, distance)
+    };
+    win.set_opacity(frame.opacity);
+    if let Some((x, y)) = position {
+        let (dx, dy) = match slide {
+            Some((Side::Top, _)) => (0, -frame.offset),
+            Some((Side::Bottom, _)) => (0, frame.offset),
+            Some((Side::Left, _)) => (-frame.offset, 0),
+            Some((Side::Right, _)) => (frame.offset, 0),
+            None => (0, 0),
+        };
+        win.move_(x + dx, y + dy);
+    }
+    reveal.is_running(now)
+}
+
+/// Calls `step` now, then on every frame until it returns false.
+fn animate(win: &ApplicationWindow, ticking: &Rc<Cell<bool>>, step: &Rc<dyn Fn() -> bool>) {
+    if !step() || ticking.get() {
+        return;
+    }
+    ticking.set(true);
+    let ticking = ticking.clone();
+    let step = step.clone();
+    win.add_tick_callback(move |_, _| {
+        if step() {
+            return ControlFlow::Continue;
+        }
+        ticking.set(false);
+        ControlFlow::Break
+    });
+}
+
+fn set_icon(image: &gtk::Image, icon: Option<&str>) {
+    match icon {
+        Some(path) if path.contains('/') => {
+            match gtk::gdk_pixbuf::Pixbuf::from_file_at_scale(path, ICON_SIZE, ICON_SIZE, true) {
+                Ok(pixbuf) => image.set_from_pixbuf(Some(&pixbuf)),
+                Err(err) => eprintln!("{NAME}: failed to load icon {path}: {err}"),
+            }
+        }
+        Some(name) => {
+            // Falls back to less specific names, e.g. `display-brightness`
+            // for `display-brightness-high`.
+            let icon = gtk::gio::ThemedIcon::with_default_fallbacks(name);
+            image.set_from_gicon(&icon, gtk::IconSize::Menu);
+            image.set_pixel_size(ICON_SIZE);
+        }
+        None => {}
+    }
+    image.set_visible(icon.is_some());
+}
+
+fn set_font(label: &gtk::Label, font_description: &str) {
+    let attr = gtk::pango::AttrList::new();
+    attr.insert(gtk::pango::AttrFontDesc::new(
+        &gtk::pango::FontDescription::from_string(font_description),
+    ));
+    label.set_attributes(Some(&attr));
+}
+
+/// Returns where the window starts along an axis of the monitor, `start` and
+/// `length` being those of the monitor and `size` that of the window.
+/// Margins go up to the edge of the window, as with the layer shell, except
+/// for the bottom anchor.
+fn place(start: i32, length: i32, size: i32, before: Option<i32>, after: Option<i32>) -> i32 {
+    match (before, after) {
+        (Some(margin), _) => start + margin,
+        (None, Some(margin)) => start + length - size - margin,
+        (None, None) => start + (length - size) / 2,
+    }
+}
+
+/// Returns the monitor to show the window on, falling back to the primary
+/// one, then to the first one.
+fn find_monitor(display: &gtk::gdk::Display, monitor: &Monitor) -> Option<gtk::gdk::Monitor> {
+    let found = match monitor {
+        Monitor::Focused => display
+            .default_seat()
+            .and_then(|seat| seat.pointer())
+            .and_then(|pointer| {
+                let (_, x, y) = pointer.position();
+                display.monitor_at_point(x, y)
+            }),
+        Monitor::Primary => display.primary_monitor(),
+        Monitor::Index(index) => i32::try_from(*index)
+            .ok()
+            .and_then(|index| display.monitor(index)),
+        // X11 gives the name of the output as the model.
+        Monitor::Name(name) => (0..display.n_monitors())
+            .filter_map(|index| display.monitor(index))
+            .find(|found| found.model().is_some_and(|model| model.as_str() == name)),
+    };
+    // Not every setup has a primary monitor, that one falls back silently.
+    if found.is_none() && *monitor != Monitor::Primary {
+        eprintln!("{NAME}: monitor {monitor} not found, using the default one");
+    }
+    found
+        .or_else(|| display.primary_monitor())
+        .or_else(|| display.monitor(0))
+}
+
+/// Places the window against the sides of the monitor given by the anchor,
+/// and remembers where in `position`.
+fn realize(
+    win: &ApplicationWindow,
+    controller: &MediaController,
+    position: &Cell<Option<(i32, i32)>>,
+) {
     let gdk_win = win.window().unwrap();
     gdk_win.set_override_redirect(true);
 
-    let monitor_geometry = gdk_win.display().primary_monitor().unwrap().geometry();
+    let Some(monitor) = find_monitor(&gdk_win.display(), &controller.monitor) else {
+        eprintln!("{NAME}: no monitor to place the window on");
+        win.show_all();
+        return;
+    };
+    let monitor_geometry = monitor.geometry();
 
-    win.move_(
-        monitor_geometry.width() / 2 - width / 2,
-        monitor_geometry.height() - bottom,
+    let margin = |side| {
+        let touches = controller.anchor.touches(side);
+        touches.then(|| controller.margin(side) as i32)
+    };
+    let x = place(
+        monitor_geometry.x(),
+        monitor_geometry.width(),
+        controller.width as i32,
+        margin(Side::Left),
+        margin(Side::Right),
     );
+    let y = match controller.anchor {
+        // `--bottom` has always gone up to the top of the window for the
+        // default anchor.
+        Anchor::Bottom => {
+            monitor_geometry.y() + monitor_geometry.height() - controller.bottom as i32
+        }
+        _ => place(
+            monitor_geometry.y(),
+            monitor_geometry.height(),
+            controller.height as i32,
+            margin(Side::Top),
+            margin(Side::Bottom),
+        ),
+    };
+    position.set(Some((x, y)));
+    win.move_(x, y);
     win.show_all();
 }
 
-fn build_ui(
-    app: &Application,
-    controller: &MediaController,
-    shared: std::sync::Arc<std::sync::Mutex<String>>,
-) {
+fn build_ui(app: &Application, controller: &MediaController, shared: SharedOsdState) {
     let w = controller.width as i32;
     let h = controller.height as i32;
-    let b = controller.bottom as i32;
 
     let win = ApplicationWindow::builder()
         .application(app)
@@ -49,33 +303,131 @@ fn build_ui(
     set_visual(&win, None);
     win.connect_screen_changed(set_visual);
     win.set_app_paintable(true);
-    win.connect_realize(move |win| realize(win, w, b));
-    let color = controller.color;
-    win.connect_draw(move |win, ctx| draw(win, ctx, color));
+    // Known once the window is realized.
+    let position = Rc::new(Cell::new(None));
+    let position_2 = position.clone();
+    let controller_2 = controller.clone();
+    win.connect_realize(move |win| realize(win, &controller_2, &position_2));
+    let shared_2 = shared.clone();
+    win.connect_draw(move |win, ctx| draw(win, ctx, &shared_2));
 
+    let image = gtk::Image::new();
+    image.set_no_show_all(true);
     let label = gtk::Label::new(None);
+    // Track titles can be longer than the window.
+    label.set_ellipsize(gtk::pango::EllipsizeMode::End);
+    let bar = gtk::DrawingArea::new();
+    bar.set_hexpand(true);
+    // Only shown while there's a level to draw.
+    bar.set_no_show_all(true);
+    let state = shared.lock().clone();
+    let animation = Rc::new(RefCell::new(BarAnimation::new(state.level)));
+    let shared_3 = shared.clone();
+    let animation_2 = animation.clone();
+    bar.connect_draw(move |area, ctx| draw_bar(area, ctx, &shared_3, &animation_2));
+    let row = gtk::Box::new(gtk::Orientation::Horizontal, BAR_SPACING);
+    row.set_margin_start(BAR_SPACING);
+    row.set_margin_end(BAR_SPACING);
+    row.pack_start(&image, false, false, 0);
+    row.pack_start(&label, false, false, 0);
+    row.pack_start(&bar, true, true, 0);
 
-    label.set_text(&shared.lock().unwrap());
-    let attr = gtk::pango::AttrList::new();
-    attr.insert(gtk::pango::AttrFontDesc::new(
-        &gtk::pango::FontDescription::from_string(&controller.font_description),
-    ));
-    label.set_attributes(Some(&attr));
-    win.add(&label);
+    let visible = state.visible;
+    set_icon(&image, state.icon.as_deref());
+    label.set_text(&state.text(state.level));
+    label.set_hexpand(state.level.is_none());
+    bar.set_visible(state.level.is_some());
+    set_font(&label, &state.font_description);
+    win.add(&row);
 
-    gtk::glib::timeout_add_local(std::time::Duration::from_millis(10), move || {
-        if let Ok(shared) = shared.lock() {
-            if label.text().as_str() != shared.as_str() {
-                label.set_text(&shared);
+    let mut reveal = Reveal::new(false);
+    if visible {
+        reveal.show(state.show_duration, Instant::now());
+    }
+    let reveal = Rc::new(RefCell::new(reveal));
+    let daemon = controller.daemon;
+    // Slides cover the margin and the window to leave the screen.
+    let slide = controller.anchor.slide_side().map(|side| {
+        let distance = match (controller.anchor, side) {
+            // The margin already covers the window.
+            (Anchor::Bottom, _) => controller.bottom as i32,
+            (_, Side::Top | Side::Bottom) => controller.margin(side) as i32 + h,
+            (_, Side::Left | Side::Right) => controller.margin(side) as i32 + w,
+        };
+        (side, distance)
+    });
+    let win_2 = win.clone();
+    let mut applied = state;
+    // Shows the current frame of the animations, and whether they go on.
+    let step: Rc<dyn Fn() -> bool> = {
+        let win = win.clone();
+        let label = label.clone();
+        let shared = shared.clone();
+        let animation = animation.clone();
+        let reveal = reveal.clone();
+        Rc::new(move || {
+            let moving = step_animation(&win, &label, &shared, &animation);
+            step_reveal(&win, &shared, &reveal, position.get(), slide) || moving
+        })
+    };
+    let ticking = Rc::new(Cell::new(false));
+    let shared_4 = shared.clone();
+    let apply = move || {
+        let state = shared_4.lock().clone();
+        if applied.level != state.level {
+            let mut animation = animation.borrow_mut();
+            animation.set_level(state.level, state.transition, state.easing, Instant::now());
+        }
+        if applied.icon != state.icon {
+            set_icon(&image, state.icon.as_deref());
+        }
+        if applied.font_description != state.font_description {
+            set_font(&label, &state.font_description);
+        }
+        if applied.level.is_some() != state.level.is_some() {
+            label.set_hexpand(state.level.is_none());
+            bar.set_visible(state.level.is_some());
+        }
+        if applied.visible != state.visible || applied.hiding != state.hiding {
+            let mut reveal = reveal.borrow_mut();
+            if !state.visible {
+                *reveal = Reveal::new(false);
+            } else if state.hiding {
+                reveal.hide(state.hide_duration, Instant::now());
+            } else {
+                reveal.show(state.show_duration, Instant::now());
             }
         }
-        ControlFlow::Continue
-    });
+        // Redrawn on every frame until the bar reaches its level and the
+        // window is done appearing or disappearing.
+        animate(&win_2, &ticking, &step);
+        if win_2.is_visible() != state.visible {
+            if state.visible {
+                win_2.show_all();
+            } else {
+                win_2.hide();
+                // Only a daemon waits for the next action.
+                if !daemon {
+                    if let Some(app) = win_2.application() {
+                        app.quit();
+                    }
+                }
+            }
+        }
+        applied = state;
+    };
 
-    win.show_all();
+    if visible {
+        win.show_all();
+    }
+
+    APPLY_STATE.with(|cell| *cell.borrow_mut() = Some(Box::new(apply)));
+    shared.connect_changed(|| MainContext::default().invoke(apply_state));
+    // Catches up with the changes made before the callback was in place.
+    apply_state();
 }
 
-pub fn spawn_window(controller: MediaController, shared: std::sync::Arc<std::sync::Mutex<String>>) {
+pub fn spawn_window(controller: MediaController, shared: SharedOsdState) {
     gtk::init().unwrap();
     let app = Application::builder().build();
     app.connect_activate(move |app| build_ui(app, &controller, shared.clone()));
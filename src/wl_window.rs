@@ -1,20 +1,218 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Instant;
+
 use gtk4::gdk::Display;
-use gtk4::glib::ControlFlow;
+use gtk4::glib::{ControlFlow, MainContext};
 use gtk4::{prelude::*, CssProvider};
 use gtk4::{Application, ApplicationWindow};
 
-use gtk4_layer_shell::LayerShell;
+use gtk4_layer_shell::{Edge, LayerShell};
+
+use crate::animation::{BarAnimation, Reveal};
+use crate::cli::NAME;
+use crate::{Color, MediaController, Monitor, SharedOsdState, Side};
+
+/// Height of the drawn progress bar relative to the window.
+const BAR_HEIGHT_RATIO: f64 = 0.5;
+const BAR_BORDER_WIDTH: f64 = 1.0;
+/// Space around the drawn progress bar in px.
+const BAR_SPACING: i32 = 8;
+const ICON_SIZE: i32 = 16;
+
+thread_local! {
+    /// Applies the shared state to the window. Only set on the GTK thread.
+    static APPLY_STATE: RefCell<Option<Box<dyn FnMut()>>> = const { RefCell::new(None) };
+}
 
-use crate::MediaController;
+/// Runs on the GTK thread after another thread changed the shared state.
+fn apply_state() {
+    APPLY_STATE.with(|apply| {
+        if let Some(apply) = apply.borrow_mut().as_mut() {
+            apply();
+        }
+    });
+}
 
-fn build_ui(
-    app: &Application,
-    controller: &MediaController,
-    shared: std::sync::Arc<std::sync::Mutex<String>>,
+fn css(color: Color) -> String {
+    format!(".media-controller-window {{ background-color: {color} }}")
+}
+
+fn set_source(ctx: &gtk4::cairo::Context, color: Color) {
+    ctx.set_source_rgba(
+        color.r as f64,
+        color.g as f64,
+        color.b as f64,
+        color.a as f64,
+    );
+}
+
+fn draw_bar(
+    ctx: &gtk4::cairo::Context,
+    width: i32,
+    height: i32,
+    shared: &SharedOsdState,
+    animation: &RefCell<BarAnimation>,
 ) {
+    let (maximum, colors) = {
+        let shared = shared.lock();
+        (shared.bar_maximum, shared.bar_colors)
+    };
+    let Some(level) = animation.borrow().level(Instant::now()) else {
+        return;
+    };
+    let width = width as f64;
+    let bar_height = height as f64 * BAR_HEIGHT_RATIO;
+    let y = (height as f64 - bar_height) / 2.0;
+    let maximum = maximum.max(1) as f64;
+    let level = level.min(maximum);
+    let step = width / maximum;
+
+    set_source(ctx, colors.track);
+    ctx.rectangle(0.0, y, width, bar_height);
+    ctx.fill().unwrap();
+
+    set_source(ctx, colors.fill);
+    ctx.rectangle(0.0, y, step * level.min(100.0), bar_height);
+    ctx.fill().unwrap();
+
+    if level > 100.0 {
+        set_source(ctx, colors.over);
+        ctx.rectangle(step * 100.0, y, step * (level - 100.0), bar_height);
+        ctx.fill().unwrap();
+    }
+
+    // Stroked inside the allocation so that it isn't clipped.
+    let inset = BAR_BORDER_WIDTH / 2.0;
+    set_source(ctx, colors.border);
+    ctx.set_line_width(BAR_BORDER_WIDTH);
+    ctx.rectangle(
+        inset,
+        y + inset,
+        width - BAR_BORDER_WIDTH,
+        bar_height - BAR_BORDER_WIDTH,
+    );
+    ctx.stroke().unwrap();
+}
+
+/// Shows the progress bar at the level it has reached. Returns whether it is
+/// still moving.
+fn step_animation(
+    label: &gtk4::Label,
+    bar: &gtk4::DrawingArea,
+    shared: &SharedOsdState,
+    animation: &RefCell<BarAnimation>,
+) -> bool {
+    let now = Instant::now();
+    let animation = animation.borrow();
+    let level = animation.level(now).map(|level| level.round() as u8);
+    let text = shared.lock().text(level);
+    if label.text().as_str() != text {
+        label.set_text(&text);
+    }
+    bar.queue_draw();
+    animation.is_running(now)
+}
+
+fn edge(side: Side) -> Edge {
+    match side {
+        Side::Top => Edge::Top,
+        Side::Bottom => Edge::Bottom,
+        Side::Left => Edge::Left,
+        Side::Right => Edge::Right,
+    }
+}
+
+/// Shows the window as far as it has appeared, either through its opacity or
+/// by lowering its `margin` on the side it slides out of, given with the
+/// distance to cover. Returns whether it is still moving.
+fn step_reveal(
+    win: &ApplicationWindow,
+    shared: &SharedOsdState,
+    reveal: &RefCell<Reveal>,
+    slide: Option<(Side, i32)>,
+    margin: i32,
+) -> bool {
+    let now = Instant::now();
+    let reveal = reveal.borrow();
+    let frame = {
+        let shared = shared.lock();
+        let distance = slide.map(|(_, distance)| distance);
+        reveal.frame(now, shared.show_animation, shared.hide_animation, distance)
+    };
+    win.set_opacity(frame.opacity);
+    if let Some((side, _)) = slide {
+        // Compositors let negative margins push the surface off the output.
+        win.set_margin(edge(side), margin - frame.offset);
+    }
+    reveal.is_running(now)
+}
+
+/// Calls `step` now, then on every frame until it returns false.
+fn animate(win: &ApplicationWindow, ticking: &Rc<Cell<bool>>, step: &Rc<dyn Fn() -> bool>) {
+    if !step() || ticking.get() {
+        return;
+    }
+    ticking.set(true);
+    let ticking = ticking.clone();
+    let step = step.clone();
+    win.add_tick_callback(move |_, _| {
+        if step() {
+            return ControlFlow::Continue;
+        }
+        ticking.set(false);
+        ControlFlow::Break
+    });
+}
+
+fn set_icon(image: &gtk4::Image, icon: Option<&str>) {
+    match icon {
+        Some(path) if path.contains('/') => image.set_from_file(Some(path)),
+        Some(name) => {
+            // Falls back to less specific names, e.g. `display-brightness`
+            // for `display-brightness-high`.
+            let icon = gtk4::gio::ThemedIcon::with_default_fallbacks(name);
+            image.set_from_gicon(&icon);
+        }
+        None => {}
+    }
+    image.set_visible(icon.is_some());
+}
+
+fn set_font(label: &gtk4::Label, font_description: &str) {
+    let attr = gtk4::pango::AttrList::new();
+    attr.insert(gtk4::pango::AttrFontDesc::new(
+        &gtk4::pango::FontDescription::from_string(font_description),
+    ));
+    label.set_attributes(Some(&attr));
+}
+
+/// Returns the monitor to show the window on. `None` leaves the choice to the
+/// compositor, which is also the fallback.
+fn find_monitor(display: &Display, monitor: &Monitor) -> Option<gtk4::gdk::Monitor> {
+    let monitors = display.monitors();
+    let mut all = (0..monitors.n_items())
+        .filter_map(|index| monitors.item(index).and_downcast::<gtk4::gdk::Monitor>());
+    let found = match monitor {
+        // Wayland has no primary monitor, and compositors already pick the
+        // focused one.
+        Monitor::Focused | Monitor::Primary => return None,
+        Monitor::Index(index) => all.nth(*index),
+        Monitor::Name(name) => all.find(|found| {
+            found
+                .connector()
+                .is_some_and(|connector| connector.as_str() == name)
+        }),
+    };
+    if found.is_none() {
+        eprintln!("{NAME}: monitor {monitor} not found, leaving the choice to the compositor");
+    }
+    found
+}
+
+fn build_ui(app: &Application, controller: &MediaController, shared: SharedOsdState) {
     let w = controller.width as i32;
     let h = controller.height as i32;
-    let b = controller.bottom as i32;
 
     let win = ApplicationWindow::builder()
         .application(app)
@@ -24,49 +222,146 @@ fn build_ui(
 
     win.init_layer_shell();
     win.set_layer(gtk4_layer_shell::Layer::Overlay);
-    win.set_anchor(gtk4_layer_shell::Edge::Bottom, true);
-    win.set_margin(gtk4_layer_shell::Edge::Bottom, b);
+    let display = Display::default().unwrap();
+    if let Some(monitor) = find_monitor(&display, &controller.monitor) {
+        win.set_monitor(Some(&monitor));
+    }
+    // The compositor centers the window along the sides it isn't anchored
+    // to.
+    for side in [Side::Top, Side::Bottom, Side::Left, Side::Right] {
+        win.set_anchor(edge(side), controller.anchor.touches(side));
+        win.set_margin(edge(side), controller.margin(side) as i32);
+    }
 
-    let css = format!(
-        ".media-controller-window {{ background-color: {} }}",
-        controller.color
-    );
+    let state = shared.lock().clone();
     let css_provider = CssProvider::new();
-    css_provider.load_from_data(css.as_str());
-    let display = Display::default().unwrap();
+    css_provider.load_from_data(&css(state.color));
     gtk4::style_context_add_provider_for_display(
         &display,
         &css_provider,
         gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION,
     );
 
+    let image = gtk4::Image::new();
+    image.set_pixel_size(ICON_SIZE);
     let label = gtk4::Label::new(None);
+    // Track titles can be longer than the window.
+    label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+    let bar = gtk4::DrawingArea::new();
+    bar.set_hexpand(true);
+    let animation = Rc::new(RefCell::new(BarAnimation::new(state.level)));
+    let shared_2 = shared.clone();
+    let animation_2 = animation.clone();
+    bar.set_draw_func(move |_, ctx, width, height| {
+        draw_bar(ctx, width, height, &shared_2, &animation_2)
+    });
+    let row = gtk4::Box::new(gtk4::Orientation::Horizontal, BAR_SPACING);
+    row.set_margin_start(BAR_SPACING);
+    row.set_margin_end(BAR_SPACING);
+    row.append(&image);
+    row.append(&label);
+    row.append(&bar);
 
-    label.set_text(&shared.lock().unwrap());
-    let attr = gtk4::pango::AttrList::new();
-    attr.insert(gtk4::pango::AttrFontDesc::new(
-        &gtk4::pango::FontDescription::from_string(&controller.font_description),
-    ));
-    label.set_attributes(Some(&attr));
-    win.set_child(Some(&label));
+    set_icon(&image, state.icon.as_deref());
+    label.set_text(&state.text(state.level));
+    label.set_hexpand(state.level.is_none());
+    bar.set_visible(state.level.is_some());
+    set_font(&label, &state.font_description);
+    win.set_child(Some(&row));
     win.add_css_class("media-controller-window");
 
-    gtk4::glib::timeout_add_local(std::time::Duration::from_millis(10), move || {
-        if let Ok(shared) = shared.lock() {
-            if label.text().as_str() != shared.as_str() {
-                label.set_text(&shared);
+    let visible = state.visible;
+    let mut reveal = Reveal::new(false);
+    if visible {
+        reveal.show(state.show_duration, Instant::now());
+    }
+    let reveal = Rc::new(RefCell::new(reveal));
+    let daemon = controller.daemon;
+    // Slides cover the margin and the window to leave the screen.
+    let slide = controller.anchor.slide_side().map(|side| {
+        let size = match side {
+            Side::Top | Side::Bottom => h,
+            Side::Left | Side::Right => w,
+        };
+        (side, controller.margin(side) as i32 + size)
+    });
+    let slide_margin = slide.map_or(0, |(side, _)| controller.margin(side) as i32);
+    let win_2 = win.clone();
+    let mut applied = state;
+    // Shows the current frame of the animations, and whether they go on.
+    let step: Rc<dyn Fn() -> bool> = {
+        let win = win.clone();
+        let label = label.clone();
+        let bar = bar.clone();
+        let shared = shared.clone();
+        let animation = animation.clone();
+        let reveal = reveal.clone();
+        Rc::new(move || {
+            let moving = step_animation(&label, &bar, &shared, &animation);
+            step_reveal(&win, &shared, &reveal, slide, slide_margin) || moving
+        })
+    };
+    let ticking = Rc::new(Cell::new(false));
+    let shared_3 = shared.clone();
+    let apply = move || {
+        let state = shared_3.lock().clone();
+        if applied.level != state.level {
+            let mut animation = animation.borrow_mut();
+            animation.set_level(state.level, state.transition, state.easing, Instant::now());
+        }
+        if applied.icon != state.icon {
+            set_icon(&image, state.icon.as_deref());
+        }
+        if applied.font_description != state.font_description {
+            set_font(&label, &state.font_description);
+        }
+        if applied.color != state.color {
+            css_provider.load_from_data(&css(state.color));
+        }
+        if applied.level.is_some() != state.level.is_some() {
+            label.set_hexpand(state.level.is_none());
+            bar.set_visible(state.level.is_some());
+        }
+        if applied.visible != state.visible || applied.hiding != state.hiding {
+            let mut reveal = reveal.borrow_mut();
+            if !state.visible {
+                *reveal = Reveal::new(false);
+            } else if state.hiding {
+                reveal.hide(state.hide_duration, Instant::now());
+            } else {
+                reveal.show(state.show_duration, Instant::now());
             }
         }
-        ControlFlow::Continue
-    });
+        // Redrawn on every frame until the bar reaches its level and the
+        // window is done appearing or disappearing.
+        animate(&win_2, &ticking, &step);
+        if win_2.is_visible() != state.visible {
+            if state.visible {
+                win_2.present();
+            } else {
+                win_2.set_visible(false);
+                // Only a daemon waits for the next action.
+                if !daemon {
+                    if let Some(app) = win_2.application() {
+                        app.quit();
+                    }
+                }
+            }
+        }
+        applied = state;
+    };
+
+    if visible {
+        win.present();
+    }
 
-    win.present();
+    APPLY_STATE.with(|cell| *cell.borrow_mut() = Some(Box::new(apply)));
+    shared.connect_changed(|| MainContext::default().invoke(apply_state));
+    // Catches up with the changes made before the callback was in place.
+    apply_state();
 }
 
-pub fn spawn_wl_window(
-    controller: MediaController,
-    shared: std::sync::Arc<std::sync::Mutex<String>>,
-) {
+pub fn spawn_wl_window(controller: MediaController, shared: SharedOsdState) {
     gtk4::init().unwrap();
     let app = Application::builder().build();
     app.connect_activate(move |app| build_ui(app, &controller, shared.clone()));